@@ -0,0 +1,87 @@
+use std::io;
+use std::time::Duration;
+
+use futures::prelude::*;
+use mavlink::common::*;
+
+use crate::{
+    mavlink_stub::{self, MavlinkConnectionHandler},
+    util::with_timeout,
+};
+
+// API
+
+/// Send a COMMAND_LONG and wait for the vehicle to acknowledge it
+///
+/// The command is repeated if no COMMAND_ACK arrives, with an increasing `confirmation` as the
+/// protocol demands. Returns the result reported by the vehicle.
+pub async fn send(
+    conn: &MavlinkConnectionHandler,
+    command: COMMAND_LONG_DATA,
+) -> io::Result<MavResult> {
+    let mut stream = conn
+        .subscribe(mavlink_stub::message_type(&MavMessage::COMMAND_ACK(
+            Default::default(),
+        )))
+        .await;
+
+    for confirmation in 0..RETRIES {
+        let message = MavMessage::COMMAND_LONG(COMMAND_LONG_DATA {
+            confirmation,
+            ..command.clone()
+        });
+        conn.send_default(&message)?;
+
+        let ack = with_timeout(TIMEOUT, "COMMAND_ACK", async {
+            while let Some(message) = stream.next().await {
+                match message {
+                    MavMessage::COMMAND_ACK(ack) if ack.command != command.command => {}
+                    // the final result follows later
+                    MavMessage::COMMAND_ACK(ack)
+                        if ack.result == MavResult::MAV_RESULT_IN_PROGRESS => {}
+                    MavMessage::COMMAND_ACK(ack) => return ack.result,
+                    _ => {}
+                }
+            }
+            unreachable!("subscriptions never end")
+        });
+        if let Ok(result) = ack.await {
+            return Ok(result);
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("{} was not acknowledged", name(command.command)),
+    ))
+}
+
+/// Turn any result but `MAV_RESULT_ACCEPTED` into an error
+pub fn check(command: MavCmd, result: MavResult) -> io::Result<()> {
+    match result {
+        MavResult::MAV_RESULT_ACCEPTED => Ok(()),
+        result => Err(io::Error::other(format!(
+            "{} was {}",
+            name(command),
+            format!("{:?}", result)
+                .trim_start_matches("MAV_RESULT_")
+                .replace('_', " ")
+                .to_lowercase()
+        ))),
+    }
+}
+
+// Implementation details
+
+/// How often a command is sent before giving up
+const RETRIES: u8 = 3;
+
+/// How long to wait for the acknowledgement of a command
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The name of a command without its prefix, e.g. `SET_MESSAGE_INTERVAL`
+fn name(command: MavCmd) -> String {
+    format!("{:?}", command)
+        .trim_start_matches("MAV_CMD_")
+        .to_string()
+}
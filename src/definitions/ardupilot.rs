@@ -20,16 +20,40 @@ struct Meta {
     version: i64,
 }
 
+/// Top level groups which belong to a vehicle rather than to a library
+const VEHICLES: &[&str] = &[
+    "ArduPlane",
+    "ArduCopter",
+    "Rover",
+    "ArduSub",
+    "AntennaTracker",
+];
+
 pub(super) fn parse(input: &str) -> io::Result<HashMap<String, Definition>> {
     let def: ArduPilotDefinitions =
         from_str(input).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
-    let mut map = HashMap::new();
+    let mut map: HashMap<String, Definition> = HashMap::new();
+
+    let mut groups: Vec<_> = def.vehicles.into_iter().collect();
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
 
-    for (vehicle, param_map) in def.vehicles {
+    for (vehicle, param_map) in groups {
+        let is_vehicle = VEHICLES.contains(&vehicle.as_str());
         for (param_name, mut param) in param_map {
             param.vehicle = vehicle.clone();
             param.name = param_name.clone();
+
+            // the same parameter may be defined for multiple vehicles
+            let mut vehicles = map
+                .remove(&param_name)
+                .map(|previous| previous.vehicles)
+                .unwrap_or_default();
+            if is_vehicle {
+                vehicles.push(vehicle.clone());
+            }
+            param.vehicles = vehicles;
+
             map.insert(param_name, param);
         }
     }
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use console::{style, Key, Term};
+use mavlink::common::MavParamType;
+
+// API
+
+/// What the user decided in the bitmask editor
+pub enum Outcome {
+    /// The new raw bits
+    Accept(u64),
+    /// The user wants to type in a value instead
+    Custom,
+    Cancel,
+}
+
+/// Number of bits which a parameter of the given type can hold
+///
+/// Floats only hold integers exactly up to the size of their mantissa. Values of all types are
+/// sent as `f32`, so integer types of more than 24 bits are only exact as long as the set bits
+/// fit into the mantissa, see [`exact`].
+pub fn width(param_type: MavParamType) -> u32 {
+    use MavParamType::*;
+    match param_type {
+        MAV_PARAM_TYPE_UINT8 | MAV_PARAM_TYPE_INT8 => 8,
+        MAV_PARAM_TYPE_UINT16 | MAV_PARAM_TYPE_INT16 => 16,
+        MAV_PARAM_TYPE_UINT32 | MAV_PARAM_TYPE_INT32 => 32,
+        MAV_PARAM_TYPE_UINT64 | MAV_PARAM_TYPE_INT64 => 64,
+        MAV_PARAM_TYPE_REAL32 | MAV_PARAM_TYPE_REAL64 => 24,
+    }
+}
+
+/// The bits of a parameter value, negative values of signed types are taken as two's complement
+pub fn to_bits(value: f32, param_type: MavParamType) -> u64 {
+    (value.round() as i64 as u64) & mask(width(param_type))
+}
+
+/// The parameter value of some bits, which is negative if the sign bit of a signed type is set
+pub fn from_bits(bits: u64, param_type: MavParamType) -> f32 {
+    use MavParamType::*;
+    let width = width(param_type);
+    let bits = bits & mask(width);
+    match param_type {
+        MAV_PARAM_TYPE_INT8 | MAV_PARAM_TYPE_INT16 | MAV_PARAM_TYPE_INT32
+        | MAV_PARAM_TYPE_INT64 => {
+            let shift = 64 - width;
+            ((bits << shift) as i64 >> shift) as f32
+        }
+        _ => bits as f32,
+    }
+}
+
+/// Whether the bits survive the conversion to the `f32` sent to the vehicle
+///
+/// For example setting bit 0 and bit 31 of an INT32 would lose bit 0. Bits beyond the width of
+/// the type are lost as well.
+pub fn exact(bits: u64, param_type: MavParamType) -> bool {
+    to_bits(from_bits(bits, param_type), param_type) == bits
+}
+
+/// Warning shown by the editors if the bits do not survive the conversion to `f32`
+pub fn inexact_warning(bits: u64, param_type: MavParamType) -> String {
+    let sent = to_bits(from_bits(bits, param_type), param_type);
+    format!(
+        "cannot be sent exactly, the vehicle would get {}",
+        hex(sent, param_type)
+    )
+}
+
+/// The bits in hexadecimal, e.g. `0x0005`, with as many digits as the type needs
+pub fn hex(bits: u64, param_type: MavParamType) -> String {
+    let digits = width(param_type).div_ceil(4) as usize;
+    format!("0x{:0w$X}", bits, w = digits)
+}
+
+/// The bits in binary, grouped in nibbles, e.g. `0000 0101`
+pub fn binary(bits: u64, param_type: MavParamType) -> String {
+    let digits = width(param_type).div_ceil(4) as usize;
+    let binary: Vec<_> = format!("{:0w$b}", bits, w = digits * 4)
+        .as_bytes()
+        .chunks(4)
+        .map(|nibble| String::from_utf8_lossy(nibble).into_owned())
+        .collect();
+    binary.join(" ")
+}
+
+/// Let the user toggle the bits of a bitmask
+///
+/// All bits the type can hold are shown, so set bits without a documented meaning are kept
+/// unless the user clears them. The value is shown in decimal, hexadecimal and binary while
+/// toggling. Bits which would not survive the conversion to `f32` are refused.
+pub fn edit(
+    name: &str,
+    labels: &BTreeMap<i64, String>,
+    bits: u64,
+    param_type: MavParamType,
+) -> io::Result<Outcome> {
+    let term = Term::stderr();
+    let width = width(param_type);
+    let mut bits = bits & mask(width);
+    let mut cursor = 0;
+    let mut lines = 0;
+
+    term.hide_cursor()?;
+    let outcome = loop {
+        term.clear_last_lines(lines)?;
+        lines = render(&term, name, labels, bits, param_type, cursor)?;
+
+        match term.read_key()? {
+            Key::ArrowUp | Key::Char('k') => cursor = cursor.checked_sub(1).unwrap_or(width - 1),
+            Key::ArrowDown | Key::Char('j') => cursor = (cursor + 1) % width,
+            Key::Char(' ') => bits ^= 1 << cursor,
+            Key::Char('a') => bits = mask(width),
+            Key::Char('n') => bits = 0,
+            Key::Char('c') => break Outcome::Custom,
+            Key::Enter if exact(bits, param_type) => break Outcome::Accept(bits),
+            Key::Escape | Key::Char('q') => break Outcome::Cancel,
+            _ => {}
+        }
+    };
+    term.clear_last_lines(lines)?;
+    term.show_cursor()?;
+    Ok(outcome)
+}
+
+// Implementation details
+
+/// Number of bits shown at once
+const PAGE: u32 = 16;
+
+fn mask(width: u32) -> u64 {
+    match width {
+        64 => u64::MAX,
+        width => (1 << width) - 1,
+    }
+}
+
+/// Draw the editor, returning the number of lines written
+fn render(
+    term: &Term,
+    name: &str,
+    labels: &BTreeMap<i64, String>,
+    bits: u64,
+    param_type: MavParamType,
+    cursor: u32,
+) -> io::Result<usize> {
+    let width = width(param_type);
+    term.write_line(&format!(
+        "{} {} {}  {} {}  {} {}",
+        style(name).bold(),
+        style("value").dim(),
+        style(from_bits(bits, param_type)).bold(),
+        style("hex").dim(),
+        hex(bits, param_type),
+        style("bin").dim(),
+        binary(bits, param_type)
+    ))?;
+
+    let first = cursor
+        .saturating_sub(PAGE / 2)
+        .min(width.saturating_sub(PAGE));
+    let last = (first + PAGE).min(width);
+    for bit in first..last {
+        let checked = bits >> bit & 1 == 1;
+        let label = match labels.get(&i64::from(bit)) {
+            Some(label) => style(label.clone()),
+            None => style(String::from("(unknown)")).dim(),
+        };
+        term.write_line(&format!(
+            "{} [{}] {:2} {}",
+            if bit == cursor {
+                style(">").cyan().bold()
+            } else {
+                style(" ")
+            },
+            if checked {
+                style("x").green().bold()
+            } else {
+                style(" ")
+            },
+            bit,
+            label
+        ))?;
+    }
+
+    let mut lines = (last - first) as usize + 2;
+    if !exact(bits, param_type) {
+        term.write_line(&format!(
+            "{}: {}",
+            style("warning").yellow().bold(),
+            inexact_warning(bits, param_type)
+        ))?;
+        lines += 1;
+    }
+    let help = "[↑↓] move  [space] toggle  [a]ll  [n]one  [c]ustom  [enter] accept  [esc] cancel";
+    term.write_line(&style(help).dim().to_string())?;
+    Ok(lines)
+}
@@ -0,0 +1,105 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::{util, vehicle::Firmware};
+
+use super::ardupilot;
+
+/// Root directory of the on-disk definition cache
+///
+/// Can be overridden with the `MAVLINK_CLI_CACHE_DIR` environment variable, defaults to
+/// `$XDG_CACHE_HOME/mavlink-cli/definitions` respectively `~/.cache/mavlink-cli/definitions`.
+pub fn dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("MAVLINK_CLI_CACHE_DIR") {
+        return PathBuf::from(dir);
+    }
+    util::xdg_dir("XDG_CACHE_HOME", ".cache").join("definitions")
+}
+
+/// Location of the definition file for a given firmware inside of the cache
+pub fn path(firmware: &Firmware) -> io::Result<PathBuf> {
+    let mut path = dir();
+    for part in &[&firmware.autopilot, &firmware.vehicle] {
+        path.push(sanitize(part)?);
+    }
+    path.push(format!("{}.json", sanitize(&firmware.version)?));
+    Ok(path)
+}
+
+/// Copy a definition file into the cache
+///
+/// The file is parsed before it is copied, so that only usable files end up in the cache.
+pub fn import(file: &Path, firmware: &Firmware) -> io::Result<PathBuf> {
+    let content = fs::read_to_string(file)?;
+    ardupilot::parse(&content)?;
+
+    let target = path(firmware)?;
+    fs::create_dir_all(target.parent().expect("cache paths always have a parent"))?;
+    fs::write(&target, content)?;
+    Ok(target)
+}
+
+/// Read the definition file for a given firmware from the cache, if there is one
+pub fn read(firmware: &Firmware) -> io::Result<Option<String>> {
+    match fs::read_to_string(path(firmware)?) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// List all firmwares for which definitions are available in the cache
+pub fn list() -> io::Result<Vec<Firmware>> {
+    let mut result = Vec::new();
+    for autopilot in sub_entries(&dir())? {
+        for vehicle in sub_entries(&autopilot)? {
+            for file in sub_entries(&vehicle)? {
+                if file.extension().is_some_and(|e| e == "json") {
+                    result.push(Firmware {
+                        autopilot: file_name(&autopilot),
+                        vehicle: file_name(&vehicle),
+                        version: file
+                            .file_stem()
+                            .map(|s| s.to_string_lossy().to_string())
+                            .unwrap_or_default(),
+                        id: None,
+                    });
+                }
+            }
+        }
+    }
+    result.sort_by(|a, b| {
+        (&a.autopilot, &a.vehicle, &a.version).cmp(&(&b.autopilot, &b.vehicle, &b.version))
+    });
+    Ok(result)
+}
+
+// Implementation details
+
+/// Make sure that a part of a cache key is usable as a single path component
+fn sanitize(part: &str) -> io::Result<&str> {
+    let mut components = Path::new(part).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(part),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{:?} is not usable as part of a cache key", part),
+        )),
+    }
+}
+
+/// All entries of a directory, or none if the directory does not exist
+fn sub_entries(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries.map(|e| e.map(|e| e.path())).collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
@@ -1,6 +1,7 @@
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap};
 use std::fmt::{self, Display, Formatter};
+use std::io;
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -12,11 +13,17 @@ use console::style;
 
 use skim::{prelude::*, DisplayContext, PreviewContext, SkimItem};
 
-use dialoguer::{Input, MultiSelect, Select};
+use dialoguer::{Confirm, Input, Select};
 
 use serde::{de, Deserialize, Deserializer};
 
+use mavlink::common::MavParamType;
+
+use crate::{ui, util, vehicle::Firmware};
+
 mod ardupilot;
+pub mod bitmask;
+pub mod cache;
 
 // Public API
 
@@ -32,8 +39,20 @@ pub struct Definition {
     pub user: User,
     #[serde(flatten)]
     pub data: Option<DataType>,
+    #[serde(default, deserialize_with = "de_bool_str")]
+    pub read_only: bool,
+    #[serde(default, deserialize_with = "de_bool_str")]
+    pub reboot_required: bool,
+    #[serde(default, alias = "DefaultValue", deserialize_with = "de_opt_number")]
+    pub default: Option<f32>,
+    /// Smallest meaningful step between two values, e.g. `0.1`
+    #[serde(default, deserialize_with = "de_opt_number")]
+    pub increment: Option<f32>,
     #[serde(default)]
     pub vehicle: String,
+    /// All vehicles this parameter is defined for, empty if it is not vehicle specific
+    #[serde(skip)]
+    pub vehicles: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -51,13 +70,23 @@ pub enum DataType {
     Values(BTreeMap<i64, String>),
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub enum User {
+    #[default]
     Standard,
     Advanced,
     User, // TODO remove this, it is a bug
 }
 
+/// A way in which a value violates the documentation of a parameter
+#[derive(Debug, Clone)]
+pub struct Violation {
+    /// Short identifier of the kind of violation, e.g. `out-of-range`
+    pub code: &'static str,
+    pub message: String,
+}
+
 /// must be called once
 pub fn init() {
     let ardupilot_included = include_str!("../../definitions/ArduPilot/result/apm.pdef.json");
@@ -65,13 +94,13 @@ pub fn init() {
     let ap = ardupilot::parse(ardupilot_included)
         .expect("parameters shipped inside binary do not parse. This is a bug. Please report it");
 
-    DEFINITIONS.store(Arc::new(ap));
+    store(ap);
 
     // iterate over all (if any) provided search paths, try to parse parameter files
     for _path in std::env::var("MAVLINK_CLI_ARDUPILOT_PATH")
         .unwrap_or_default()
         .split(':')
-        .map(|s| Path::new(s))
+        .map(Path::new)
     {
         // TODO implement file level parser as well
     }
@@ -79,10 +108,48 @@ pub fn init() {
     // TODO implement the same for PX4
 }
 
+/// Replace the current definitions with the ones matching the given firmware
+///
+/// The definitions are taken from the on-disk cache, see the `cache` module. If the cache does not
+/// contain a matching file, a `NotFound` error is returned and the current definitions are kept.
+pub fn load(firmware: &Firmware) -> io::Result<()> {
+    let not_found = || {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no cached definitions for {}", firmware),
+        )
+    };
+
+    // TODO implement the same for PX4
+    if firmware.autopilot != "ArduPilot" {
+        return Err(not_found());
+    }
+
+    let content = cache::read(firmware)?.ok_or_else(not_found)?;
+    store(ardupilot::parse(&content)?);
+    Ok(())
+}
+
 /// show information about a definiton
+///
+/// Parameters without a definition of their own, e.g. `SERVO14_FUNCTION`, are resolved through the
+/// family they belong to, e.g. `SERVO{n}_FUNCTION`, as long as their index is not below the first
+/// one of the family.
 // TODO Return avoid cloning
 pub fn lookup(param_name: &str) -> Option<Definition> {
-    DEFINITIONS.load().get(param_name).cloned()
+    if let Some(def) = DEFINITIONS.load().get(param_name) {
+        return Some(def.clone());
+    }
+
+    let families = FAMILIES.load();
+    split_indices(param_name)
+        .into_iter()
+        .find_map(|(template, index)| {
+            let family = families.get(&template)?;
+            family
+                .contains(index)
+                .then(|| family.definition.instantiate(param_name, index))
+        })
 }
 
 /// return all defintions
@@ -98,28 +165,146 @@ pub fn all() -> Vec<Definition> {
 pub static DEFINITIONS: Lazy<ArcSwap<HashMap<String, Definition>>> =
     Lazy::new(|| ArcSwap::from_pointee(HashMap::new()));
 
+/// Atomical Reference Counter which holding the indexed parameter families, keyed by their name
+/// with the index replaced by `INDEX_PLACEHOLDER`. This is derived from the definitions and must not
+/// be mutated by concurrent code.
+static FAMILIES: Lazy<ArcSwap<HashMap<String, Family>>> =
+    Lazy::new(|| ArcSwap::from_pointee(HashMap::new()));
+
+/// Placeholder for the index in templated definitions, e.g. `SERVO{n}_FUNCTION`
+pub const INDEX_PLACEHOLDER: &str = "{n}";
+
+/// The definitions are global, tests storing them must not run at the same time
+#[cfg(test)]
+pub(crate) static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
 // Implementation
 
+/// Parameters which only differ by an index, e.g. `SERVO1_FUNCTION` to `SERVO16_FUNCTION`
+#[derive(Debug)]
+struct Family {
+    /// The definition its members without a definition of their own are derived from
+    definition: Definition,
+    /// The smallest index defined, `None` if unknown
+    first: Option<u32>,
+}
+
+impl Family {
+    fn contains(&self, index: u32) -> bool {
+        self.first.is_none_or(|first| index >= first)
+    }
+}
+
+/// Replace the currently known definitions and derive the families from them
+///
+/// Definitions containing `INDEX_PLACEHOLDER` in their name are templates of a family. They only
+/// serve to resolve other names and are not known by their own name. Other definitions with an
+/// index form a family with all definitions which only differ by that index, e.g.
+/// `SERVO1_FUNCTION` stands for `SERVO14_FUNCTION` as well, since the parameter documentation often
+/// describes the first instance only. The definition with the lowest index is then used for the
+/// members of the family lacking one. Either way, indices below the lowest one defined are no
+/// members of a family, as instances are counted from 0 or 1. Templates without any concrete
+/// sibling accept every index.
+fn store(definitions: HashMap<String, Definition>) {
+    let (templates, definitions): (HashMap<_, _>, HashMap<_, _>) = definitions
+        .into_iter()
+        .partition(|(name, _)| name.contains(INDEX_PLACEHOLDER));
+
+    let mut names: Vec<_> = definitions.keys().collect();
+    names.sort_by(|a, b| util::natural_cmp(a, b));
+    let mut siblings: HashMap<String, Vec<(u32, &Definition)>> = HashMap::new();
+    for name in names {
+        for (template, index) in split_indices(name) {
+            siblings
+                .entry(template)
+                .or_default()
+                .push((index, &definitions[name]));
+        }
+    }
+
+    let mut families: HashMap<_, _> = siblings
+        .into_iter()
+        .map(|(template, members)| {
+            let first = members.iter().map(|(index, _)| *index).min();
+            let family = Family {
+                definition: members[0].1.clone(),
+                first,
+            };
+            (template, family)
+        })
+        .collect();
+    for (template, definition) in templates {
+        let first = families.remove(&template).and_then(|family| family.first);
+        families.insert(template, Family { definition, first });
+    }
+
+    DEFINITIONS.store(Arc::new(definitions));
+    FAMILIES.store(Arc::new(families));
+}
+
+/// All ways to read a parameter name as member of a family
+///
+/// Each run of digits may be the index, e.g. `SR1_EXTRA3` is either `(SR{n}_EXTRA3, 1)` or
+/// `(SR1_EXTRA{n}, 3)`. Digits with a leading zero are no index.
+fn split_indices(name: &str) -> Vec<(String, u32)> {
+    let mut result = Vec::new();
+    let mut rest = name.char_indices().peekable();
+    while let Some((start, c)) = rest.next() {
+        if !c.is_ascii_digit() {
+            continue;
+        }
+        let mut end = start + 1;
+        while let Some((i, _)) = rest.next_if(|(_, c)| c.is_ascii_digit()) {
+            end = i + 1;
+        }
+        let digits = &name[start..end];
+        match digits.parse::<u32>() {
+            Ok(index) if index.to_string() == digits => result.push((
+                format!("{}{}{}", &name[..start], INDEX_PLACEHOLDER, &name[end..]),
+                index,
+            )),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Replace each `INDEX_PLACEHOLDER` in `text` by the next index
+///
+/// Surplus placeholders are replaced by the last index.
+pub fn fill_indices(text: &str, indices: &[&str]) -> String {
+    let mut parts = text.split(INDEX_PLACEHOLDER);
+    let mut result = parts.next().unwrap_or_default().to_string();
+    for (i, part) in parts.enumerate() {
+        result.push_str(indices.get(i).or_else(|| indices.last()).unwrap_or(&""));
+        result.push_str(part);
+    }
+    result
+}
+
 impl Definition {
+    /// Create the definition of one member of the family this definition is the template of
+    fn instantiate(&self, name: &str, index: u32) -> Definition {
+        let index = index.to_string();
+        Definition {
+            name: name.to_string(),
+            description: fill_indices(&self.description, &[&index]),
+            display_name: fill_indices(&self.display_name, &[&index]),
+            ..self.clone()
+        }
+    }
+
     /// interacts with the user, allowing a new value to be found
-    pub fn interact(&self, current_value: f32) -> f32 {
+    ///
+    /// The `param_type` of the parameter on the vehicle determines how many bits a bitmask has.
+    pub fn interact(&self, current_value: f32, param_type: MavParamType) -> f32 {
         match &self.data {
             // no information is available about this parameter data type
-            None => {
-                let mut input = Input::new();
-                input
-                    .with_initial_text(current_value.to_string())
-                    .with_prompt(&self.name);
-                input.interact().unwrap_or(current_value)
-            }
+            None => self.input(current_value, param_type, self.name.clone()),
             // parameter is a float in a given interval
             Some(DataType::Range { high, low }) => {
-                let mut input = Input::new();
-                input
-                    .with_initial_text(current_value.to_string())
-                    .with_prompt(format!("{} [{} {}]", &self.name, low, high));
-                // TODO What if the user gives a wrong value? Give at least a warning?
-                input.interact().unwrap_or(current_value)
+                let prompt = format!("{} [{} {}]", &self.name, low, high);
+                self.input(current_value, param_type, prompt)
             }
             // parameter is one value out of a given set
             Some(DataType::Values(values)) => {
@@ -137,11 +322,7 @@ impl Definition {
                 match select.interact_opt() {
                     // user wants to enter a custom value
                     Ok(Some(selection)) if selection == items.len() - 1 => {
-                        let mut input = Input::new();
-                        input
-                            .with_initial_text(current_value.to_string())
-                            .with_prompt(self.name.to_string());
-                        input.interact().unwrap_or(current_value)
+                        self.input(current_value, param_type, self.name.clone())
                     }
                     // user chose one of the provided values
                     Ok(Some(selection)) => items[selection].0 as f32,
@@ -151,36 +332,12 @@ impl Definition {
             }
             // parameter is a (sub-) set of given values, combined in a bitmask
             Some(DataType::Bitmask(values)) => {
-                let items = Selection::map_to_vec(values.iter());
-                let mut select = MultiSelect::new();
-                select.paged(true).with_prompt(&self.name);
-
-                // find already selected items
-                let original = current_value.round() as i64;
-                for bit in 0..32 {
-                    let item = values
-                        .get(&bit)
-                        .unwrap_or(&format!("Bit {} (unknown)", bit))
-                        .clone();
-                    select.item_checked(Selection(bit, item), original >> bit & 1 == 1);
-                }
-
-                match select.interact() {
+                let bits = bitmask::to_bits(current_value, param_type);
+                match bitmask::edit(&self.name, values, bits, param_type) {
+                    Ok(bitmask::Outcome::Accept(bits)) => bitmask::from_bits(bits, param_type),
                     // user wants to enter a custom value
-                    Ok(selection) if selection.contains(&(items.len() - 1)) => {
-                        let mut input = Input::new();
-                        input
-                            .with_initial_text(current_value.to_string())
-                            .with_prompt(self.name.to_string());
-                        input.interact().unwrap_or(current_value)
-                    }
-                    // user chose some of the provided values
-                    Ok(selection) => {
-                        let mut bytes: i64 = 0;
-                        for s in selection {
-                            bytes |= 1 << items[s].0;
-                        }
-                        bytes as f32
+                    Ok(bitmask::Outcome::Custom) => {
+                        self.input(current_value, param_type, self.name.clone())
                     }
                     _ => current_value,
                 }
@@ -188,6 +345,184 @@ impl Definition {
         }
     }
 
+    /// Ask the user for a value until it is valid, or was explicitly confirmed
+    ///
+    /// Values which are not numbers are rejected right away. Values which violate the
+    /// documentation, e.g. by being out of range, are explained and have to be confirmed.
+    fn input(&self, current_value: f32, param_type: MavParamType, prompt: String) -> f32 {
+        let mut initial = current_value.to_string();
+        loop {
+            let mut input = Input::<String>::new();
+            input.with_initial_text(&initial).with_prompt(&prompt);
+            let text = match input.interact() {
+                Ok(text) => text,
+                Err(_) => return current_value,
+            };
+
+            let value = match text.trim().parse::<f32>() {
+                Ok(value) if value.is_finite() => value,
+                _ => {
+                    ui::error(&format!("{:?} is not a number", text));
+                    initial = text;
+                    continue;
+                }
+            };
+
+            let violations = self.violations(value, Some(param_type));
+            if violations.is_empty() {
+                return value;
+            }
+            for violation in &violations {
+                ui::warning(&violation.to_string());
+            }
+            let confirmed = Confirm::new()
+                .with_prompt(format!("Set {} to {} anyway?", self.name, value))
+                .default(false)
+                .interact()
+                .unwrap_or(false);
+            if confirmed {
+                return value;
+            }
+            initial = text;
+        }
+    }
+
+    /// Parse a value given as a number or by the names of its meanings
+    ///
+    /// Values may be given by their name, e.g. `MAVLink2`, bitmasks by a list of bit names
+    /// separated by `,` or `|`, e.g. `GPS,IMU`. Names are compared case-insensitively. Bits are
+    /// set as the `param_type` of the parameter stores them, bits which it can not hold or which
+    /// would be lost as `f32` are refused.
+    pub fn parse_value(&self, text: &str, param_type: MavParamType) -> Result<f32, String> {
+        let text = text.trim();
+        if let Ok(value) = text.parse::<f32>() {
+            return match value.is_finite() {
+                true => Ok(value),
+                false => Err(format!("{} is not a finite number", text)),
+            };
+        }
+
+        let find = |mapping: &BTreeMap<i64, String>, name: &str| {
+            mapping
+                .iter()
+                .find(|(_, v)| v.trim().eq_ignore_ascii_case(name.trim()))
+                .map(|(k, _)| *k)
+                .ok_or_else(|| {
+                    let known: Vec<_> = mapping.values().map(String::as_str).collect();
+                    format!(
+                        "{:?} is not known for {}, try one of: {}",
+                        name.trim(),
+                        self.name,
+                        known.join(", ")
+                    )
+                })
+        };
+
+        match &self.data {
+            Some(DataType::Values(values)) => find(values, text).map(|k| k as f32),
+            Some(DataType::Bitmask(bits)) => {
+                let mut mask: u64 = 0;
+                for name in text.split([',', '|']).filter(|n| !n.trim().is_empty()) {
+                    mask |= 1 << find(bits, name)?;
+                }
+                match bitmask::exact(mask, param_type) {
+                    true => Ok(bitmask::from_bits(mask, param_type)),
+                    false => Err(format!(
+                        "{:?} {}",
+                        text,
+                        bitmask::inexact_warning(mask, param_type)
+                    )),
+                }
+            }
+            _ => Err(format!("{:?} is not a number", text)),
+        }
+    }
+
+    /// Explain in which ways a value violates the documentation of this parameter
+    ///
+    /// The type of the parameter tells which bits of a bitmask a negative value sets, if it is
+    /// known.
+    pub fn violations(&self, value: f32, param_type: Option<MavParamType>) -> Vec<Violation> {
+        let mut result = Vec::new();
+        let mut violation = |code, message| result.push(Violation { code, message });
+        let integer = value.fract() == 0.0;
+
+        match &self.data {
+            Some(DataType::Range { high, low }) if value < *low || value > *high => violation(
+                "out-of-range",
+                format!("{} is outside of [{} - {}]", value, low, high),
+            ),
+            Some(DataType::Values(values)) if !integer || !values.contains_key(&(value as i64)) => {
+                violation(
+                    "invalid-value",
+                    format!("{} is none of the documented values", value),
+                )
+            }
+            Some(DataType::Bitmask(_)) if !integer => violation(
+                "invalid-bitmask",
+                format!("{} is not an integer, as a bitmask is", value),
+            ),
+            Some(DataType::Bitmask(bits)) => {
+                let unknown: Vec<_> = set_bits(value, param_type)
+                    .filter(|bit| !bits.contains_key(bit))
+                    .map(|bit| bit.to_string())
+                    .collect();
+                if !unknown.is_empty() {
+                    violation(
+                        "unknown-bits",
+                        format!(
+                            "{} sets the undocumented bits {}",
+                            value,
+                            unknown.join(", ")
+                        ),
+                    );
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(increment) = self.increment.filter(|i| *i > 0.0) {
+            let base = match &self.data {
+                Some(DataType::Range { low, .. }) => *low,
+                _ => 0.0,
+            };
+            let steps = (value - base) / increment;
+            // allow for the imprecision of f32, e.g. 0.3 / 0.1 = 2.9999998
+            if (steps - steps.round()).abs() > 1e-3 {
+                violation(
+                    "increment",
+                    format!("{} is not a multiple of {}", value, increment),
+                );
+            }
+        }
+
+        result
+    }
+
+    /// Describe a value of this parameter, including its meaning if it is documented
+    ///
+    /// For example `3 (Copter)` for values or `5 (Gyro, Baro)` for bitmasks.
+    pub fn describe_value(&self, value: f32, param_type: Option<MavParamType>) -> String {
+        let meaning = match &self.data {
+            Some(DataType::Values(values)) => values.get(&(value as i64)).cloned(),
+            Some(DataType::Bitmask(bits)) => {
+                let checked: Vec<_> = set_bits(value, param_type)
+                    .map(|bit| {
+                        bits.get(&bit)
+                            .cloned()
+                            .unwrap_or_else(|| format!("Bit {} (unknown)", bit))
+                    })
+                    .collect();
+                Some(checked.join(", "))
+            }
+            _ => None,
+        };
+        match meaning {
+            Some(meaning) => format!("{} ({})", value, meaning),
+            None => value.to_string(),
+        }
+    }
+
     pub fn name(&self) -> String {
         format!("{:-16}", style(&self.name).bold())
     }
@@ -202,11 +537,11 @@ impl Definition {
             }
             Some(DataType::Values(mapping)) | Some(DataType::Bitmask(mapping)) => {
                 let max_key_length = mapping
-                    .iter()
-                    .map(|(k, _)| k.to_string().len())
+                    .keys()
+                    .map(|k| k.to_string().len())
                     .max()
                     .unwrap_or(0);
-                let max_value_length = mapping.iter().map(|(_, v)| v.len()).max().unwrap_or(0);
+                let max_value_length = mapping.values().map(|v| v.len()).max().unwrap_or(0);
                 let joint = " = ";
                 let cols = width / (max_key_length + max_value_length + joint.len());
                 let cols = std::cmp::max(1, cols);
@@ -216,7 +551,7 @@ impl Definition {
                 }
 
                 (0..rows)
-                    .map(|initial_offset| {
+                    .flat_map(|initial_offset| {
                         mapping
                             .iter()
                             .skip(initial_offset)
@@ -234,7 +569,6 @@ impl Definition {
                                 )
                             })
                     })
-                    .flatten()
                     .collect()
             }
             None => String::from(""),
@@ -245,11 +579,11 @@ impl Definition {
 }
 
 impl SkimItem for Definition {
-    fn display(&self, _context: DisplayContext) -> AnsiString {
+    fn display(&self, _context: DisplayContext) -> AnsiString<'_> {
         AnsiString::parse(&self.name())
     }
 
-    fn text(&self) -> Cow<str> {
+    fn text(&self) -> Cow<'_, str> {
         let all_text = format!(
             "{} {} {} {}",
             self.name, self.display_name, self.description, self.vehicle
@@ -263,6 +597,12 @@ impl SkimItem for Definition {
     }
 }
 
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
 impl Display for Definition {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -279,7 +619,7 @@ impl PartialEq for Definition {
 
 impl Ord for Definition {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.name.cmp(&other.name)
+        util::natural_cmp(&self.name, &other.name)
     }
 }
 
@@ -289,12 +629,6 @@ impl PartialOrd for Definition {
     }
 }
 
-impl Default for User {
-    fn default() -> Self {
-        User::Standard
-    }
-}
-
 struct Selection(i64, String);
 
 impl Selection {
@@ -319,7 +653,7 @@ impl Display for Selection {
 }
 
 /// custom deserializer to parse something from a String
-fn de_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+pub(crate) fn de_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
 where
     D: Deserializer<'de>,
     T: FromStr,
@@ -329,6 +663,44 @@ where
     s.parse().map_err(serde::de::Error::custom)
 }
 
+/// The bits a bitmask value sets, as many as the type of the parameter holds
+///
+/// Parameter files do not tell the type, ArduPilot's bitmasks have up to 32 bits then.
+fn set_bits(value: f32, param_type: Option<MavParamType>) -> impl Iterator<Item = i64> {
+    let param_type = param_type.unwrap_or(MavParamType::MAV_PARAM_TYPE_INT32);
+    let bits = bitmask::to_bits(value, param_type);
+    (0..bitmask::width(param_type))
+        .filter(move |bit| bits >> bit & 1 == 1)
+        .map(i64::from)
+}
+
+/// custom deserializer to parse an optional number from either a number or a String
+fn de_opt_number<'de, D>(deserializer: D) -> Result<Option<f32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Number {
+        Number(f32),
+        String(String),
+    }
+
+    match Number::deserialize(deserializer)? {
+        Number::Number(n) => Ok(Some(n)),
+        Number::String(s) => s.trim().parse().map(Some).map_err(de::Error::custom),
+    }
+}
+
+/// custom deserializer to parse a bool from a String like `True`
+fn de_bool_str<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    s.to_lowercase().parse().map_err(serde::de::Error::custom)
+}
+
 /// custom deserializer to parse a key from String
 fn de_int_key<'de, D, K, V>(deserializer: D) -> Result<BTreeMap<K, V>, D::Error>
 where
@@ -345,3 +717,151 @@ where
     }
     Ok(map)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(name: &str, description: &str) -> Definition {
+        let mut definition: Definition = serde_json::from_value(serde_json::json!({
+            "Description": description,
+            "DisplayName": description,
+        }))
+        .unwrap();
+        definition.name = name.to_string();
+        definition
+    }
+
+    #[test]
+    fn split_indices_reads_every_run_of_digits_as_index() {
+        assert_eq!(
+            split_indices("SERVO14_FUNCTION"),
+            vec![(String::from("SERVO{n}_FUNCTION"), 14)]
+        );
+        assert_eq!(
+            split_indices("SR1_EXTRA3"),
+            vec![
+                (String::from("SR{n}_EXTRA3"), 1),
+                (String::from("SR1_EXTRA{n}"), 3)
+            ]
+        );
+        assert_eq!(split_indices("BATT2"), vec![(String::from("BATT{n}"), 2)]);
+        assert!(split_indices("ARMING_CHECK").is_empty());
+        assert!(split_indices("SERVO01_FUNCTION").is_empty());
+    }
+
+    #[test]
+    fn lookup_resolves_members_of_families_only() {
+        let _lock = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let names = [
+            ("SERVO1_FUNCTION", "servo function"),
+            ("SERVO2_FUNCTION", "servo function"),
+            ("SERVO16_FUNCTION", "servo function"),
+            ("SERVO{n}_MIN", "minimum of servo {n}"),
+            ("SR0_EXTRA1", "extra 1"),
+            ("SR2_EXTRA1", "extra 1"),
+            ("SR0_EXTRA3", "extra 3"),
+            ("SR2_EXTRA3", "extra 3"),
+            ("EK2_ENABLE", "EKF2 enable"),
+            ("EK3_ENABLE", "EKF3 enable"),
+            ("EK2_ALT_SOURCE", "EKF2 altitude source"),
+        ];
+        store(
+            names
+                .iter()
+                .map(|(name, description)| (name.to_string(), definition(name, description)))
+                .collect(),
+        );
+
+        let description = |name| lookup(name).map(|def| def.description);
+        assert_eq!(description("SERVO2_FUNCTION").unwrap(), "servo function");
+        let servo5 = lookup("SERVO5_FUNCTION").unwrap();
+        assert_eq!(servo5.name, "SERVO5_FUNCTION");
+        assert_eq!(servo5.description, "servo function");
+        assert_eq!(description("SERVO7_MIN").unwrap(), "minimum of servo 7");
+        assert_eq!(description("SR1_EXTRA3").unwrap(), "extra 3");
+        assert_eq!(description("SR1_EXTRA1").unwrap(), "extra 1");
+
+        // beyond the documented instances
+        assert_eq!(description("SERVO17_FUNCTION").unwrap(), "servo function");
+        assert_eq!(description("SERVO99_FUNCTION").unwrap(), "servo function");
+        assert_eq!(description("SR3_EXTRA3").unwrap(), "extra 3");
+        // a single definition is the template of its family
+        assert_eq!(
+            description("EK3_ALT_SOURCE").unwrap(),
+            "EKF2 altitude source"
+        );
+        // below the first instance of the family
+        assert_eq!(description("SERVO0_FUNCTION"), None);
+        assert_eq!(description("EK1_ALT_SOURCE"), None);
+        // no family, just a similar name
+        assert_eq!(description("SR1_EXTRA2"), None);
+        // templates are no parameters of their own
+        assert_eq!(description("SERVO{n}_MIN"), None);
+
+        let names: Vec<_> = all().into_iter().map(|def| def.name).collect();
+        assert!(names.iter().all(|name| !name.contains(INDEX_PLACEHOLDER)));
+        let servos: Vec<_> = names.iter().filter(|n| n.starts_with("SERVO")).collect();
+        assert_eq!(
+            servos,
+            ["SERVO1_FUNCTION", "SERVO2_FUNCTION", "SERVO16_FUNCTION"]
+        );
+    }
+
+    #[test]
+    fn lookup_resolves_undocumented_instances_of_the_shipped_definitions() {
+        let _lock = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        init();
+
+        assert!(DEFINITIONS.load().get("SERVO14_FUNCTION").is_none());
+        let servo14 = lookup("SERVO14_FUNCTION").unwrap();
+        assert_eq!(servo14.name, "SERVO14_FUNCTION");
+        let servo1 = lookup("SERVO1_FUNCTION").unwrap();
+        assert_eq!(servo14.display_name, servo1.display_name);
+        assert!(matches!(servo14.data, Some(DataType::Values(_))));
+    }
+
+    #[test]
+    fn bitmasks_have_the_width_of_their_type() {
+        use MavParamType::*;
+        let mut def = definition("LOG_BITMASK", "log bitmask");
+        let labels = (0..8).map(|bit| (bit, format!("Bit{}", bit))).collect();
+        def.data = Some(DataType::Bitmask(labels));
+
+        assert!(def.violations(-1.0, Some(MAV_PARAM_TYPE_INT8)).is_empty());
+        let codes: Vec<_> = def
+            .violations(-1.0, Some(MAV_PARAM_TYPE_INT16))
+            .into_iter()
+            .map(|violation| violation.code)
+            .collect();
+        assert_eq!(codes, ["unknown-bits"]);
+        assert_eq!(
+            def.describe_value(-1.0, Some(MAV_PARAM_TYPE_INT8)),
+            "-1 (Bit0, Bit1, Bit2, Bit3, Bit4, Bit5, Bit6, Bit7)"
+        );
+
+        assert!(bitmask::exact(1 << 31, MAV_PARAM_TYPE_INT32));
+        assert!(!bitmask::exact(1 << 31 | 1, MAV_PARAM_TYPE_INT32));
+        assert!(!bitmask::exact(1 << 24 | 1, MAV_PARAM_TYPE_UINT32));
+        assert!(bitmask::exact(0xFF_FFFF, MAV_PARAM_TYPE_UINT32));
+        assert!(!bitmask::exact(1 << 8, MAV_PARAM_TYPE_UINT8));
+
+        let labels = [(0, "Fast"), (7, "Slow"), (8, "High"), (31, "Top")];
+        let labels = labels.iter().map(|(bit, name)| (*bit, name.to_string()));
+        def.data = Some(DataType::Bitmask(labels.collect()));
+        assert_eq!(
+            def.parse_value("fast, slow", MAV_PARAM_TYPE_INT8),
+            Ok(-127.0)
+        );
+        assert_eq!(
+            def.parse_value("Fast|Slow", MAV_PARAM_TYPE_INT16),
+            Ok(129.0)
+        );
+        assert!(def.parse_value("High", MAV_PARAM_TYPE_UINT8).is_err());
+        assert_eq!(
+            def.parse_value("Top", MAV_PARAM_TYPE_INT32),
+            Ok(i32::MIN as f32)
+        );
+        assert!(def.parse_value("Top,Fast", MAV_PARAM_TYPE_INT32).is_err());
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use chrono::prelude::*;
+use clap::Clap;
+use serde_json::{Map, Value};
+
+use crate::{fields, listen, mavlink_stub::Frame, tlog, ui};
+
+// API
+
+/// Which messages of a log to export, which of their fields and where to
+#[derive(Clap, Debug)]
+pub struct Options {
+    /// The telemetry log (tlog) to export
+    #[clap()]
+    pub file: PathBuf,
+
+    /// Directory to write a CSV file per message type to, e.g. `ATTITUDE.csv`
+    #[clap(short, long, required_unless_present = "json")]
+    pub out: Option<PathBuf>,
+
+    /// Only export these fields, e.g. `roll,pitch` or `ATTITUDE.roll,VFR_HUD.alt`, or items of
+    /// arrays, e.g. `q[0]`. Messages without any of them are skipped.
+    #[clap(short, long = "field", use_delimiter = true, number_of_values = 1)]
+    pub fields: Vec<FieldName>,
+
+    /// Skip the messages before this time, given as offset from the start of the log, e.g. `90`
+    /// or `1:30`, or as RFC 3339 time, e.g. `2021-03-14T15:09:26+01:00`
+    #[clap(long)]
+    pub from: Option<Time>,
+
+    /// Skip the messages after this time, in the format of --from
+    #[clap(long)]
+    pub to: Option<Time>,
+
+    #[clap(flatten)]
+    pub filter: listen::Options,
+}
+
+/// A field, optionally of a specific message, e.g. `ATTITUDE.roll`, or an item of an array field,
+/// e.g. `q[0]`
+#[derive(Debug, Clone)]
+pub struct FieldName {
+    message: Option<String>,
+    field: String,
+    index: Option<usize>,
+}
+
+/// A point in time of a log
+#[derive(Debug, Clone, Copy)]
+pub enum Time {
+    /// Time since the first message of the log
+    Offset(Duration),
+    At(SystemTime),
+}
+
+/// Export the messages of a log, as JSON lines to stdout with --json and as CSV files otherwise
+pub fn export(options: &Options) -> io::Result<()> {
+    options.filter.validate()?;
+    let mut output = match &options.out {
+        Some(dir) if !options.filter.json => {
+            fs::create_dir_all(dir)?;
+            Output::Csv(dir.clone(), HashMap::new())
+        }
+        _ => Output::Json(BufWriter::new(io::stdout())),
+    };
+
+    let mut start = None;
+    let mut errors = 0;
+    for frame in tlog::Reader::open(&options.file)? {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(_) => {
+                errors += 1;
+                continue;
+            }
+        };
+        let start = *start.get_or_insert(frame.time);
+        if options
+            .from
+            .is_some_and(|from| frame.time < from.resolve(start))
+        {
+            continue;
+        }
+        // not the end yet, the frames of a log are not strictly ordered by time
+        if options.to.is_some_and(|to| frame.time > to.resolve(start)) {
+            continue;
+        }
+        if !options.filter.matches(&frame) {
+            continue;
+        }
+        let fields = match options.select(&frame) {
+            Some(fields) => fields,
+            None => continue,
+        };
+        match output.write(&frame, fields) {
+            // e.g. piped into `head`
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            result => result?,
+        }
+    }
+    output.finish()?;
+
+    if errors > 0 {
+        ui::warning(&format!(
+            "skipped {} frames which could not be parsed",
+            errors
+        ));
+    }
+    Ok(())
+}
+
+impl Options {
+    /// The fields of a frame to export, `None` if it has none of the requested fields
+    fn select(&self, frame: &Frame) -> Option<Map<String, Value>> {
+        let fields = fields::fields(&frame.message);
+        if self.fields.is_empty() {
+            return Some(fields);
+        }
+        let message = fields::message_name(&frame.message);
+        let mut selected = Map::new();
+        for (field, value) in fields {
+            for name in self.fields.iter().filter(|f| f.matches(&message, &field)) {
+                match name.index {
+                    None => drop(selected.insert(field.clone(), value.clone())),
+                    // named like the columns of `flatten`
+                    Some(index) => {
+                        if let Some(item) = value.get(index) {
+                            selected.insert(format!("{}[{}]", field, index), item.clone());
+                        }
+                    }
+                }
+            }
+        }
+        Some(selected).filter(|selected| !selected.is_empty())
+    }
+}
+
+impl FieldName {
+    fn matches(&self, message: &str, field: &str) -> bool {
+        self.field == field
+            && self
+                .message
+                .as_ref()
+                .is_none_or(|m| m.eq_ignore_ascii_case(message))
+    }
+}
+
+impl FromStr for FieldName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (message, field) = match s.trim().split_once('.') {
+            Some((message, field)) => (Some(message.to_string()), field),
+            None => (None, s.trim()),
+        };
+        let err = || format!("{:?} is not of the form [MESSAGE.]FIELD[[INDEX]]", s);
+        let (field, index) = match field.strip_suffix(']').and_then(|f| f.split_once('[')) {
+            Some((field, index)) => (field, Some(index.parse().map_err(|_| err())?)),
+            None => (field, None),
+        };
+        match field.is_empty() || message.as_deref() == Some("") {
+            true => Err(err()),
+            false => Ok(FieldName {
+                message,
+                field: field.to_string(),
+                index,
+            }),
+        }
+    }
+}
+
+impl Time {
+    /// The point in time in a log which started at `start`
+    pub fn resolve(self, start: SystemTime) -> SystemTime {
+        match self {
+            Time::Offset(offset) => start + offset,
+            Time::At(time) => time,
+        }
+    }
+}
+
+impl FromStr for Time {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(time) = DateTime::parse_from_rfc3339(s.trim()) {
+            return Ok(Time::At(time.into()));
+        }
+        // seconds, minutes and hours from the right
+        let mut seconds = 0.0;
+        for (part, unit) in s.trim().rsplit(':').zip([1.0, 60.0, 3600.0].iter()) {
+            match part.parse::<f64>() {
+                Ok(value) if value >= 0.0 && value.is_finite() => seconds += value * unit,
+                _ => return Err(format!("{:?} is neither [[HH:]MM:]SS nor RFC 3339", s)),
+            }
+        }
+        match s.matches(':').count() {
+            0..=2 => Ok(Time::Offset(Duration::from_secs_f64(seconds))),
+            _ => Err(format!("{:?} is neither [[HH:]MM:]SS nor RFC 3339", s)),
+        }
+    }
+}
+
+// Implementation details
+
+enum Output {
+    Json(BufWriter<io::Stdout>),
+    /// The directory and the open file per message type
+    Csv(PathBuf, HashMap<String, Table>),
+}
+
+/// A CSV file, its columns are fixed by the first message written
+struct Table {
+    file: BufWriter<File>,
+    columns: Vec<String>,
+}
+
+impl Output {
+    fn write(&mut self, frame: &Frame, fields: Map<String, Value>) -> io::Result<()> {
+        match self {
+            Output::Json(out) => writeln!(out, "{}", listen::to_json(frame, fields)),
+            Output::Csv(dir, tables) => {
+                let name = fields::message_name(&frame.message);
+                let values = flatten(fields);
+                let table = match tables.get_mut(&name) {
+                    Some(table) => table,
+                    None => {
+                        let table = Table::create(&dir.join(format!("{}.csv", name)), &values)?;
+                        tables.entry(name).or_insert(table)
+                    }
+                };
+                table.write(frame, &values)
+            }
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Output::Json(mut out) => out.flush(),
+            Output::Csv(_, tables) => tables
+                .into_values()
+                .try_for_each(|mut table| table.file.flush()),
+        }
+    }
+}
+
+impl Table {
+    fn create(path: &Path, values: &[(String, Value)]) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let columns: Vec<_> = values.iter().map(|(name, _)| name.clone()).collect();
+        let header: Vec<_> = ["time", "sysid", "compid"]
+            .iter()
+            .map(|column| column.to_string())
+            .chain(columns.iter().map(|column| quote(column)))
+            .collect();
+        writeln!(file, "{}", header.join(","))?;
+        Ok(Table { file, columns })
+    }
+
+    fn write(&mut self, frame: &Frame, values: &[(String, Value)]) -> io::Result<()> {
+        let time: DateTime<Local> = frame.time.into();
+        let mut row = vec![
+            time.to_rfc3339_opts(SecondsFormat::Micros, false),
+            frame.header.system_id.to_string(),
+            frame.header.component_id.to_string(),
+        ];
+        // columns missing in this message stay empty
+        row.extend(self.columns.iter().map(|column| {
+            values
+                .iter()
+                .find(|(name, _)| name == column)
+                .map_or_else(String::new, |(_, value)| quote(&fields::to_text(value)))
+        }));
+        writeln!(self.file, "{}", row.join(","))
+    }
+}
+
+/// The fields as columns, arrays split into a column per item, e.g. `q[0]`
+fn flatten(fields: Map<String, Value>) -> Vec<(String, Value)> {
+    let mut columns = Vec::new();
+    for (name, value) in fields {
+        match value {
+            Value::Array(items) => columns.extend(
+                items
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, item)| (format!("{}[{}]", name, i), item)),
+            ),
+            value => columns.push((name, value)),
+        }
+    }
+    columns
+}
+
+/// Quote a CSV value if necessary
+fn quote(value: &str) -> String {
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mavlink::{common::*, MavHeader};
+    use serde_json::json;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn field_names_may_name_the_message_and_an_index() {
+        let name: FieldName = "ATTITUDE.roll".parse().unwrap();
+        assert_eq!(name.message.as_deref(), Some("ATTITUDE"));
+        assert_eq!((name.field.as_str(), name.index), ("roll", None));
+        assert!(name.matches("ATTITUDE", "roll"));
+        assert!(!name.matches("ATTITUDE", "pitch"));
+        assert!(!name.matches("AHRS2", "roll"));
+
+        let name: FieldName = " q[2]".parse().unwrap();
+        assert_eq!(name.message, None);
+        assert_eq!((name.field.as_str(), name.index), ("q", Some(2)));
+        assert!(name.matches("ATTITUDE_TARGET", "q"));
+
+        for invalid in &["", ".roll", "ATTITUDE.", "q[a]", "q[-1]", "q[]"] {
+            assert!(invalid.parse::<FieldName>().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn selected_items_are_named_like_the_csv_columns() {
+        let options = Options::parse_from([
+            "export",
+            "x.tlog",
+            "--json",
+            "-f",
+            "q[1],q[9],ATTITUDE_TARGET.thrust",
+        ]);
+        let frame = |message| Frame {
+            header: MavHeader::default(),
+            message,
+            time: UNIX_EPOCH,
+            raw: None,
+        };
+        let target = frame(MavMessage::ATTITUDE_TARGET(ATTITUDE_TARGET_DATA {
+            q: [1.0, 0.5, 0.0, 0.0],
+            thrust: 0.25,
+            ..Default::default()
+        }));
+        let selected = options.select(&target).unwrap();
+        assert_eq!(
+            Value::Object(selected),
+            json!({"q[1]": 0.5, "thrust": 0.25})
+        );
+
+        let heartbeat = frame(MavMessage::HEARTBEAT(Default::default()));
+        assert!(options.select(&heartbeat).is_none());
+    }
+
+    #[test]
+    fn times_are_offsets_or_rfc3339() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let resolve = |s: &str| s.parse::<Time>().map(|time| time.resolve(start));
+
+        assert_eq!(resolve("90"), Ok(start + Duration::from_secs(90)));
+        assert_eq!(resolve("1.5"), Ok(start + Duration::from_millis(1500)));
+        assert_eq!(resolve("1:30"), Ok(start + Duration::from_secs(90)));
+        assert_eq!(resolve("1:00:00"), Ok(start + Duration::from_secs(3600)));
+        assert_eq!(
+            resolve("1970-01-01T01:00:00+01:00"),
+            Ok(UNIX_EPOCH),
+            "the log start does not matter"
+        );
+        for invalid in &["", "-1", "a", "1:2:3:4", "1::2", "inf"] {
+            assert!(resolve(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use mavlink::common::MavMessage;
+use serde_json::{Map, Value};
+
+/// The name of a message, e.g. `ATTITUDE`
+pub fn message_name(message: &MavMessage) -> String {
+    let debug = format!("{:?}", message);
+    debug
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// The decoded fields of a message by name
+///
+/// Enums are given by the name of their value, bitmasks by their numeric value and character
+/// arrays as strings.
+pub fn fields(message: &MavMessage) -> Map<String, Value> {
+    let mut fields = match serde_json::to_value(message) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    };
+    // the message name is stored as tag
+    fields.remove("type");
+    fields
+        .into_iter()
+        .map(|(name, value)| (name, simplify(value)))
+        .collect()
+}
+
+/// Look up a field of a message as it is shown by `fields`
+pub fn field(message: &MavMessage, name: &str) -> Option<Value> {
+    fields(message).remove(name)
+}
+
+/// A value as plain text, strings without quotes
+pub fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+// Implementation details
+
+/// Unwrap the serde representations of enums, bitflags and character arrays
+fn simplify(value: Value) -> Value {
+    match value {
+        Value::Object(mut object) if object.len() == 1 => {
+            match (object.remove("type"), object.remove("bits")) {
+                (Some(tag), _) => tag,
+                (_, Some(bits)) => bits,
+                _ => Value::Object(object),
+            }
+        }
+        Value::Array(items) if !items.is_empty() && items.iter().all(is_char) => {
+            let text: String = items
+                .iter()
+                .filter_map(Value::as_str)
+                .collect::<String>()
+                .trim_end_matches('\0')
+                .to_string();
+            Value::String(text)
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(simplify).collect()),
+        // most fields are `f32`, show them with the digits they actually have
+        Value::Number(number) if number.is_f64() => {
+            let value = number.as_f64().unwrap_or_default();
+            let shortest = (value as f32).to_string().parse().unwrap_or(value);
+            match value as f32 as f64 == value {
+                true => Value::from(shortest),
+                false => Value::Number(number),
+            }
+        }
+        other => other,
+    }
+}
+
+fn is_char(value: &Value) -> bool {
+    matches!(value, Value::String(s) if s.chars().count() == 1)
+}
@@ -0,0 +1,270 @@
+use std::io;
+
+use mavlink::common::MavParamType;
+use serde::Serialize;
+
+use crate::{
+    mavlink_stub::MavlinkConnectionHandler, parameters::Parameter, push_pull, report::Session, ui,
+};
+
+// API
+
+/// A parameter as printed by `get`
+#[derive(Debug, Clone, Serialize)]
+pub struct Value {
+    pub name: String,
+    pub value: f32,
+    /// The type of the parameter on the vehicle, e.g. `INT16`
+    #[serde(rename = "type")]
+    pub param_type: String,
+    /// The value including its documented meaning, e.g. `2 (MAVLink2)`
+    pub meaning: String,
+}
+
+/// The outcome of one assignment of `set`
+#[derive(Debug, Clone, Serialize)]
+pub struct Assignment {
+    pub name: String,
+    pub old: Option<f32>,
+    pub new: Option<f32>,
+    pub confirmed: bool,
+    /// Why the parameter was not set, if it was not
+    pub error: Option<String>,
+}
+
+/// Read parameters from the vehicle and print them to stdout
+///
+/// Each of the `names` is either the name of a parameter, which is read on its own, or a
+/// pattern with `*` and `?` wildcards, for which all parameters are read. The plain output is
+/// in the format of `pull`. Returns whether every name was found.
+pub async fn get(
+    conn: &MavlinkConnectionHandler,
+    names: &[String],
+    json: bool,
+) -> io::Result<bool> {
+    let mut result = Vec::new();
+    let mut success = true;
+
+    let (patterns, exact): (Vec<_>, Vec<_>) = names
+        .iter()
+        .map(|n| n.to_uppercase())
+        .partition(|n| n.contains(['*', '?']));
+
+    for name in exact {
+        match Parameter::fetch(conn, &name).await {
+            Ok(param) => result.push(param),
+            Err(e) => {
+                ui::error(&e.to_string());
+                success = false;
+            }
+        }
+    }
+
+    if !patterns.is_empty() {
+        let all = push_pull::fetch_parameters(conn).await?;
+        for pattern in patterns {
+            let matching: Vec<_> = all.iter().filter(|p| glob(&pattern, &p.name)).collect();
+            if matching.is_empty() {
+                ui::error(&format!("no parameter matches {}", pattern));
+                success = false;
+            }
+            result.extend(matching.into_iter().cloned());
+        }
+    }
+
+    let values: Vec<_> = result.iter().map(Value::from).collect();
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(&values)?),
+        false => {
+            for value in values {
+                println!("{},{}", value.name, value.value);
+            }
+        }
+    }
+    Ok(success)
+}
+
+/// Set parameters on the vehicle from assignments like `NAME=VALUE`
+///
+/// Values may be given by name, see `Definition::parse_value`. Values which violate the
+/// definition of a parameter are refused, unless `force` is given. Every change is confirmed by
+/// reading back the value and recorded in the `session`. Returns whether all assignments were
+/// confirmed.
+pub async fn set(
+    conn: &MavlinkConnectionHandler,
+    assignments: &[String],
+    json: bool,
+    force: bool,
+    session: &mut Session,
+) -> io::Result<bool> {
+    let mut results = Vec::new();
+    for assignment in assignments {
+        let result = assign(conn, assignment, force, session).await?;
+        if !json {
+            match (&result.error, result.old, result.new) {
+                (Some(error), ..) => ui::error(error),
+                (None, Some(old), Some(new)) if result.confirmed => {
+                    println!("{}: {} -> {}", result.name, old, new)
+                }
+                (None, _, Some(new)) => ui::error(&format!(
+                    "{} = {} was not confirmed by the vehicle",
+                    result.name, new
+                )),
+                _ => {}
+            }
+        }
+        results.push(result);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    }
+    Ok(results.iter().all(|r| r.confirmed))
+}
+
+impl From<&Parameter> for Value {
+    fn from(param: &Parameter) -> Self {
+        Value {
+            name: param.name.clone(),
+            value: param.value,
+            param_type: type_name(param.param_type),
+            meaning: param
+                .definition()
+                .describe_value(param.value, Some(param.param_type)),
+        }
+    }
+}
+
+// Implementation details
+
+async fn assign(
+    conn: &MavlinkConnectionHandler,
+    assignment: &str,
+    force: bool,
+    session: &mut Session,
+) -> io::Result<Assignment> {
+    let (name, text) = match assignment.split_once('=') {
+        Some((name, text)) => (name.trim().to_uppercase(), text),
+        None => {
+            return Ok(Assignment::failed(
+                assignment,
+                format!("{:?} is not of the form NAME=VALUE", assignment),
+            ))
+        }
+    };
+
+    let mut param = match Parameter::fetch(conn, &name).await {
+        Ok(param) => param,
+        Err(e) => return Ok(Assignment::failed(&name, e.to_string())),
+    };
+    let def = param.definition();
+    let old = param.value;
+
+    let value = match def.parse_value(text, param.param_type) {
+        Ok(value) => value,
+        Err(e) => return Ok(Assignment::failed(&name, e)),
+    };
+
+    if def.read_only {
+        return Ok(Assignment::failed(&name, format!("{} is read only", name)));
+    }
+    let violations: Vec<_> = def
+        .violations(value, Some(param.param_type))
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    if !violations.is_empty() && !force {
+        return Ok(Assignment::failed(
+            &name,
+            format!(
+                "{}: {}, use --force to set it anyway",
+                name,
+                violations.join(", ")
+            ),
+        ));
+    }
+
+    param.value = value;
+    let confirmed = param.push_confirmed(conn).await?;
+    session.record(&param, Some(old), confirmed);
+    Ok(Assignment {
+        name,
+        old: Some(old),
+        new: Some(value),
+        confirmed,
+        error: None,
+    })
+}
+
+impl Assignment {
+    fn failed(name: &str, error: String) -> Self {
+        Assignment {
+            name: name.to_string(),
+            old: None,
+            new: None,
+            confirmed: false,
+            error: Some(error),
+        }
+    }
+}
+
+/// Match a name against a pattern with `*` (any number of characters) and `?` (one character)
+fn glob(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<_>, Vec<_>) = (pattern.chars().collect(), name.chars().collect());
+    // position after the last `*` and the position in name it was matched up to
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // let the last `*` swallow one more character
+                Some((after, matched)) => {
+                    star = Some((after, matched + 1));
+                    p = after;
+                    n = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn type_name(param_type: MavParamType) -> String {
+    format!("{:?}", param_type)
+        .trim_start_matches("MAV_PARAM_TYPE_")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matches_wildcards() {
+        assert!(glob("SERVO1_FUNCTION", "SERVO1_FUNCTION"));
+        assert!(!glob("SERVO1_FUNCTION", "SERVO1_FUNCTIONS"));
+        assert!(glob("SERVO*_FUNCTION", "SERVO14_FUNCTION"));
+        assert!(glob("SERVO*_FUNCTION", "SERVO_FUNCTION"));
+        assert!(!glob("SERVO*_FUNCTION", "SERVO1_MIN"));
+        assert!(glob("SERVO?_MIN", "SERVO3_MIN"));
+        assert!(!glob("SERVO?_MIN", "SERVO13_MIN"));
+        assert!(!glob("SERVO?_MIN", "SERVO_MIN"));
+        assert!(glob("*", ""));
+        assert!(glob("BATT*", "BATT2_MONITOR"));
+        assert!(glob("*_MIN", "SERVO1_MIN"));
+        // the first `*` must not stop the second from backtracking
+        assert!(glob("S*O*_MIN", "SERVO10_MIN"));
+        assert!(glob("*A*A*", "BANANA"));
+        assert!(!glob("*A*A*A*A", "BANANA"));
+    }
+}
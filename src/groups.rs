@@ -0,0 +1,286 @@
+use std::sync::Arc;
+
+use console::style;
+use skim::{prelude::*, DisplayContext, PreviewContext, SkimItem};
+
+use crate::{definitions::Definition, parameters::Parameter, util};
+
+// API
+
+/// Anything which can be sorted into a parameter group
+pub trait Grouped {
+    /// Name of the parameter, e.g. `EK3_SRC1_POSXY`
+    fn param_name(&self) -> &str;
+
+    /// ArduPilot library group of the parameter, e.g. `EK3_`
+    fn library_group(&self) -> String;
+}
+
+/// A node in the tree of parameter groups
+///
+/// The tree is built from the library group of each parameter, and then from the `_` separated
+/// prefixes of the parameter name. Groups with a single member are not worth drilling down into,
+/// so their member is lifted into the parent group.
+#[derive(Debug, Clone)]
+pub struct Group<T> {
+    /// Full path to this group, e.g. `EK3_/SRC1`
+    pub path: String,
+    pub name: String,
+    pub groups: Vec<Arc<Group<T>>>,
+    pub items: Vec<T>,
+}
+
+/// One entry shown in the fuzzy finder while browsing the group tree
+pub enum Entry<T> {
+    /// Go back to the parent group
+    Up,
+    Group(Arc<Group<T>>),
+    Item(T),
+}
+
+impl<T: Grouped + Clone> Group<T> {
+    /// Build the group tree from a list of items
+    pub fn tree(items: &[T]) -> Self {
+        let mut root = Builder::new();
+        for item in items {
+            let library_group = item.library_group();
+            let trimmed = library_group.trim_end_matches('_');
+            let segments: Vec<_> = item.param_name().split('_').collect();
+
+            let mut node = root.child(&library_group);
+            for (i, segment) in segments[..segments.len() - 1].iter().enumerate() {
+                // avoid `EK3_/EK3`, the library group already covers the first segment
+                if i == 0 && *segment == trimmed {
+                    continue;
+                }
+                node = node.child(segment);
+            }
+            node.items.push(item.clone());
+        }
+        root.build(String::new(), String::new())
+    }
+
+    /// Number of items in this group and all of its subgroups
+    pub fn len(&self) -> usize {
+        self.items.len() + self.groups.iter().map(|g| g.len()).sum::<usize>()
+    }
+
+    /// All items in this group and all of its subgroups
+    pub fn all_items(&self) -> Vec<T> {
+        let mut result = self.items.clone();
+        for group in &self.groups {
+            result.extend(group.all_items());
+        }
+        result
+    }
+
+    /// The group at `path` within this group, e.g. `EK3_/SRC1`
+    pub fn find(self: &Arc<Self>, path: &str) -> Option<Arc<Self>> {
+        if self.path == path {
+            return Some(self.clone());
+        }
+        self.groups.iter().find_map(|group| group.find(path))
+    }
+
+    /// The entries to show when browsing this group
+    pub fn entries(self: &Arc<Self>, is_root: bool) -> Vec<Entry<T>> {
+        let up = if is_root { None } else { Some(Entry::Up) };
+        up.into_iter()
+            .chain(self.groups.iter().cloned().map(Entry::Group))
+            .chain(self.items.iter().cloned().map(Entry::Item))
+            .collect()
+    }
+
+    /// Describes the group
+    pub fn description(&self, width: usize) -> String {
+        let title = style(&self.path).bold().underlined();
+        let description = describe(&self.name)
+            .map(|d| format!("{}\n\n", textwrap::fill(d, width)))
+            .unwrap_or_default();
+
+        let members: String = self
+            .groups
+            .iter()
+            .map(|g| format!("{}/ ({})\n", style(&g.name).bold(), g.len()))
+            .chain(self.items.iter().map(|i| format!("{}\n", i.param_name())))
+            .collect();
+
+        format!(
+            "{}\n\n{}{} parameters\n\n{}",
+            title,
+            description,
+            self.len(),
+            members
+        )
+    }
+}
+
+impl Grouped for Definition {
+    fn param_name(&self) -> &str {
+        &self.name
+    }
+
+    fn library_group(&self) -> String {
+        self.vehicle.clone()
+    }
+}
+
+impl Grouped for Parameter {
+    fn param_name(&self) -> &str {
+        &self.name
+    }
+
+    fn library_group(&self) -> String {
+        self.definition().vehicle
+    }
+}
+
+// Implementation details
+
+struct Builder<T> {
+    children: Vec<(String, Builder<T>)>,
+    items: Vec<T>,
+}
+
+impl<T: Grouped + Clone> Builder<T> {
+    fn new() -> Self {
+        Builder {
+            children: Vec::new(),
+            items: Vec::new(),
+        }
+    }
+
+    fn child(&mut self, name: &str) -> &mut Self {
+        let index = match self.children.iter().position(|(n, _)| n == name) {
+            Some(index) => index,
+            None => {
+                self.children.push((name.to_string(), Builder::new()));
+                self.children.len() - 1
+            }
+        };
+        &mut self.children[index].1
+    }
+
+    fn build(self, path: String, name: String) -> Group<T> {
+        let mut groups = Vec::new();
+        let mut items = self.items;
+
+        for (child_name, child) in self.children {
+            let child_path = match path.is_empty() {
+                true => child_name.clone(),
+                false => format!("{}/{}", path, child_name),
+            };
+            let group = child.build(child_path, child_name);
+            match group.len() {
+                0 => {}
+                1 => items.extend(group.all_items()),
+                _ => groups.push(Arc::new(group)),
+            }
+        }
+
+        groups.sort_by(|a, b| util::natural_cmp(&a.name, &b.name));
+        items.sort_by(|a, b| util::natural_cmp(a.param_name(), b.param_name()));
+
+        Group {
+            path,
+            name,
+            groups,
+            items,
+        }
+    }
+}
+
+/// Short descriptions of well known ArduPilot parameter groups
+fn describe(group: &str) -> Option<&'static str> {
+    let group: String = group
+        .trim_end_matches('_')
+        .chars()
+        .filter(|c| !c.is_ascii_digit())
+        .collect();
+    let description = match group.as_str() {
+        "AHRS" => "Attitude and heading reference system",
+        "ARMING" => "Arming checks",
+        "ATC" => "Attitude controller",
+        "AVOID" => "Object avoidance",
+        "BARO" => "Barometers",
+        "BATT" => "Battery monitors",
+        "BRD" => "Board specific settings",
+        "CAM" => "Camera triggering",
+        "CAN" => "CAN bus drivers",
+        "COMPASS" => "Compasses",
+        "EK" => "Extended Kalman filter (EKF) navigation",
+        "FENCE" => "Geofence",
+        "FLTMODE" => "Flight modes",
+        "FS" => "Failsafes",
+        "GPS" => "GPS receivers",
+        "INS" => "Inertial sensors",
+        "LOG" => "Onboard logging",
+        "MNT" => "Camera mounts and gimbals",
+        "MOT" => "Motors",
+        "NTF" => "Notification devices like LEDs and buzzers",
+        "PSC" => "Position controller",
+        "Q" => "QuadPlane",
+        "RC" => "RC input channels",
+        "RCMAP" => "RC channel mapping",
+        "RNGFND" => "Rangefinders",
+        "RTL" => "Return to launch",
+        "SCHED" => "Scheduler",
+        "SERIAL" => "Serial ports",
+        "SERVO" => "Servo outputs",
+        "SR" => "Telemetry stream rates",
+        "TECS" => "Total energy control system",
+        "TERRAIN" => "Terrain following",
+        "WPNAV" => "Waypoint navigation",
+        _ => return None,
+    };
+    Some(description)
+}
+
+impl<T: Grouped> Entry<T> {
+    /// Identifies the entry within a group
+    pub fn key(&self) -> String {
+        match self {
+            Entry::Up => String::from(".."),
+            Entry::Group(group) => format!("{}/", group.path),
+            Entry::Item(item) => item.param_name().to_string(),
+        }
+    }
+}
+
+impl<T> SkimItem for Entry<T>
+where
+    T: SkimItem + Grouped + Clone,
+{
+    fn display<'a>(&'a self, context: DisplayContext<'a>) -> AnsiString<'a> {
+        match self {
+            Entry::Up => AnsiString::parse(&format!("{}", style("../").bold().blue())),
+            Entry::Group(group) => AnsiString::parse(&format!(
+                "{} ({})",
+                style(format!("{}/", group.name)).bold().blue(),
+                group.len()
+            )),
+            Entry::Item(item) => item.display(context),
+        }
+    }
+
+    fn text(&self) -> Cow<'_, str> {
+        match self {
+            Entry::Up => Cow::Borrowed(".."),
+            Entry::Group(group) => Cow::Owned(format!(
+                "{}\n{}",
+                group.name,
+                describe(&group.name).unwrap_or_default()
+            )),
+            Entry::Item(item) => item.text(),
+        }
+    }
+
+    fn preview(&self, context: PreviewContext) -> ItemPreview {
+        let width = textwrap::termwidth() / 2 - 1;
+        match self {
+            Entry::Up => ItemPreview::Text(String::from("go back to the parent group")),
+            Entry::Group(group) => ItemPreview::AnsiText(group.description(width)),
+            Entry::Item(item) => item.preview(context),
+        }
+    }
+}
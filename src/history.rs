@@ -0,0 +1,123 @@
+/// Key to undo the last change applied to the vehicle
+pub const UNDO_KEY: &str = "ctrl-z";
+
+/// Key to redo the last undone change
+pub const REDO_KEY: &str = "ctrl-y";
+
+/// The changes applied to the vehicle during a `configure` session, for undo and redo
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+/// A parameter changed from `old` to `new`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edit {
+    pub name: String,
+    pub old: f32,
+    pub new: f32,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a change which was applied to the vehicle
+    ///
+    /// This forgets all changes which were undone before, as they can no longer be redone.
+    pub fn record(&mut self, name: &str, old: f32, new: f32) {
+        self.redo.clear();
+        self.undo.push(Edit {
+            name: name.to_string(),
+            old,
+            new,
+        });
+    }
+
+    /// The last change to revert, the parameter has to be set to its `old` value
+    ///
+    /// The change stays to be undone until `undone` is called, once the vehicle confirmed it.
+    pub fn undo(&self) -> Option<&Edit> {
+        self.undo.last()
+    }
+
+    /// The last reverted change, the parameter has to be set to its `new` value again
+    ///
+    /// The change stays to be redone until `redone` is called, once the vehicle confirmed it.
+    pub fn redo(&self) -> Option<&Edit> {
+        self.redo.last()
+    }
+
+    /// Move the change returned by `undo` to the changes which can be redone
+    pub fn undone(&mut self) {
+        if let Some(edit) = self.undo.pop() {
+            self.redo.push(edit);
+        }
+    }
+
+    /// Move the change returned by `redo` back to the changes which can be undone
+    pub fn redone(&mut self) {
+        if let Some(edit) = self.redo.pop() {
+            self.undo.push(edit);
+        }
+    }
+
+    /// A hint on the available undo and redo steps, to be shown to the user
+    pub fn hint(&self) -> Option<String> {
+        let undo = self
+            .undo
+            .last()
+            .map(|e| format!("[{}] undo {} back to {}", UNDO_KEY, e.name, e.old));
+        let redo = self
+            .redo
+            .last()
+            .map(|e| format!("[{}] redo {} = {}", REDO_KEY, e.name, e.new));
+        match (undo, redo) {
+            (Some(undo), Some(redo)) => Some(format!("{}, {}", undo, redo)),
+            (undo, redo) => undo.or(redo),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(name: &str, old: f32, new: f32) -> Edit {
+        Edit {
+            name: name.to_string(),
+            old,
+            new,
+        }
+    }
+
+    #[test]
+    fn changes_move_between_undo_and_redo_once_confirmed() {
+        let mut history = History::new();
+        assert_eq!(history.undo(), None);
+        history.record("A", 1.0, 2.0);
+        history.record("B", 3.0, 4.0);
+
+        // a failed undo leaves the history as it is
+        assert_eq!(history.undo(), Some(&edit("B", 3.0, 4.0)));
+        assert_eq!(history.redo(), None);
+
+        history.undone();
+        assert_eq!(history.undo(), Some(&edit("A", 1.0, 2.0)));
+        assert_eq!(history.redo(), Some(&edit("B", 3.0, 4.0)));
+        history.undone();
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.hint().unwrap(), "[ctrl-y] redo A = 2",);
+
+        history.redone();
+        assert_eq!(history.undo(), Some(&edit("A", 1.0, 2.0)));
+        assert_eq!(history.redo(), Some(&edit("B", 3.0, 4.0)));
+
+        // a new change can not be followed by the undone ones
+        history.record("C", 5.0, 6.0);
+        assert_eq!(history.redo(), None);
+        assert_eq!(history.hint().unwrap(), "[ctrl-z] undo C back to 5",);
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use console::style;
+use serde::Serialize;
+
+use crate::{
+    definitions,
+    parameters::Parameter,
+    push_pull,
+    rules::{self, Rule},
+};
+
+// API
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A single finding in a parameter file
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Short identifier of the check which failed, e.g. `out-of-range`
+    pub code: &'static str,
+    pub message: String,
+    pub line: usize,
+    pub parameter: Option<String>,
+}
+
+/// The result of linting a parameter file
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub file: String,
+    pub diagnostics: Vec<Diagnostic>,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+/// Check every entry of a parameter file against the known definitions
+///
+/// # Arguments
+///
+/// * `in_file` - parameter file in the format written by `pull`
+/// * `vehicle` - if given, parameters which only exist for other vehicles are reported
+/// * `rules` - consistency rules to check the whole file against
+pub fn lint(in_file: &Path, vehicle: Option<&str>, rules: &[Rule]) -> io::Result<Report> {
+    let file = BufReader::new(File::open(in_file)?);
+
+    let mut diagnostics = Vec::new();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut parameters = Vec::new();
+
+    for (index, line) in file.lines().enumerate() {
+        let line_number = index + 1;
+        let param = match push_pull::parse_line(&line?, line_number) {
+            Ok(Some(param)) => param,
+            Ok(None) => continue,
+            Err(e) => {
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    code: "syntax",
+                    message: e.to_string(),
+                    line: line_number,
+                    parameter: None,
+                });
+                continue;
+            }
+        };
+
+        let mut report = |severity, code, message| {
+            diagnostics.push(Diagnostic {
+                severity,
+                code,
+                message,
+                line: line_number,
+                parameter: Some(param.name.clone()),
+            })
+        };
+
+        if let Some(first) = seen.insert(param.name.clone(), line_number) {
+            report(
+                Severity::Error,
+                "duplicate",
+                format!("{} was already set in line {}", param.name, first),
+            );
+        }
+
+        for (severity, code, message) in check(&param, vehicle) {
+            report(severity, code, message);
+        }
+        parameters.push(param);
+    }
+
+    for violation in rules::check(rules, &parameters) {
+        let line = violation
+            .parameters
+            .first()
+            .and_then(|name| seen.get(name))
+            .copied()
+            .unwrap_or_default();
+        diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            code: "rule",
+            message: violation.to_string(),
+            line,
+            parameter: violation.parameters.first().cloned(),
+        });
+    }
+
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    Ok(Report {
+        file: in_file.display().to_string(),
+        warnings: diagnostics.len() - errors,
+        errors,
+        diagnostics,
+    })
+}
+
+impl Report {
+    /// Print all diagnostics to stderr in a compiler like fashion
+    pub fn print(&self) {
+        for d in &self.diagnostics {
+            let severity = match d.severity {
+                Severity::Warning => style("warning").yellow().bold(),
+                Severity::Error => style("error").red().bold(),
+            };
+            eprintln!(
+                "{}{}: {}",
+                severity,
+                style(format!("[{}]", d.code)).bold(),
+                style(&d.message).bold()
+            );
+            eprintln!(
+                "  {} {}:{}\n",
+                style("-->").blue().bold(),
+                self.file,
+                d.line
+            );
+        }
+        eprintln!(
+            "{}: {} errors, {} warnings",
+            self.file, self.errors, self.warnings
+        );
+    }
+}
+
+// Implementation details
+
+/// Check a single parameter against its definition
+fn check(param: &Parameter, vehicle: Option<&str>) -> Vec<(Severity, &'static str, String)> {
+    let mut result = Vec::new();
+    let (name, value) = (&param.name, &param.value);
+
+    let def = match definitions::lookup(name) {
+        Some(def) => def,
+        None => {
+            result.push((
+                Severity::Warning,
+                "unknown",
+                format!("{} is an unknown parameter", name),
+            ));
+            return result;
+        }
+    };
+
+    if def.read_only {
+        result.push((
+            Severity::Error,
+            "read-only",
+            format!("{} is read only", name),
+        ));
+    }
+
+    if let Some(vehicle) = vehicle {
+        if !def.vehicles.is_empty() && !def.vehicles.iter().any(|v| v == vehicle) {
+            result.push((
+                Severity::Error,
+                "vehicle-mismatch",
+                format!(
+                    "{} is not available on {}, only on {}",
+                    name,
+                    vehicle,
+                    def.vehicles.join(", ")
+                ),
+            ));
+        }
+    }
+
+    // parameter files do not tell the type of the parameters
+    for violation in def.violations(*value, None) {
+        let severity = match violation.code {
+            // the increment is a recommendation rather than a limit
+            "increment" => Severity::Warning,
+            _ => Severity::Error,
+        };
+        result.push((
+            severity,
+            violation.code,
+            format!("{} = {}", name, violation),
+        ));
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint_lines(lines: &[&str]) -> Report {
+        let path = std::env::temp_dir().join(format!("mavlink-cli-{}.param", std::process::id()));
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let report = lint(&path, None, &rules::load(None).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        report.unwrap()
+    }
+
+    #[test]
+    fn diagnostics_tell_the_line_and_check() {
+        let _lock = definitions::TEST_LOCK
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        definitions::init();
+
+        let report = lint_lines(&[
+            "# comment",
+            "SERVO1_FUNCTION,1",
+            "SERVO2_FUNCTION,7",
+            "SERVO3_MIN,100",
+            "SERVO1_FUNCTION,0",
+            "NO_SUCH_PARAM,1",
+            "SERVO4_MIN",
+            "BATT_MONITOR,4",
+            "BATT_CAPACITY,0",
+        ]);
+        let diagnostics: Vec<_> = report
+            .diagnostics
+            .iter()
+            .map(|d| (d.line, d.code, d.severity))
+            .collect();
+        assert_eq!(
+            diagnostics,
+            [
+                (3, "invalid-value", Severity::Error),
+                (4, "out-of-range", Severity::Error),
+                (5, "duplicate", Severity::Error),
+                (6, "unknown", Severity::Warning),
+                (7, "syntax", Severity::Error),
+                (8, "unknown", Severity::Warning),
+                (9, "unknown", Severity::Warning),
+                (8, "rule", Severity::Warning),
+            ]
+        );
+        assert_eq!((report.errors, report.warnings), (4, 4));
+        assert_eq!(
+            report.diagnostics[1].parameter.as_deref(),
+            Some("SERVO3_MIN")
+        );
+        assert_eq!(report.diagnostics[4].parameter, None);
+    }
+}
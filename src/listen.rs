@@ -0,0 +1,195 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use chrono::prelude::*;
+use clap::Clap;
+use console::style;
+use futures::prelude::*;
+use mavlink::{common::MavMessage, Message};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    fields,
+    mavlink_stub::{Frame, MavlinkConnectionHandler},
+    rules::Op,
+};
+
+// API
+
+/// Which of the received messages to print and how
+#[derive(Clap, Debug, Clone)]
+pub struct Options {
+    /// Only print these messages, e.g. ATTITUDE,GPS_RAW_INT
+    #[clap(short, long = "message", use_delimiter = true, number_of_values = 1)]
+    pub messages: Vec<String>,
+
+    /// Only print messages of this system
+    #[clap(long)]
+    pub sysid: Option<u8>,
+
+    /// Only print messages of this component
+    #[clap(long)]
+    pub compid: Option<u8>,
+
+    /// Only print messages whose fields fulfill a condition, e.g. `alt>1000` or
+    /// `fix_type==3D_FIX`. Supports ==, !=, <, <=, > and >=, may be given multiple times.
+    #[clap(short = 'w', long = "where", number_of_values = 1)]
+    pub conditions: Vec<Condition>,
+
+    /// Print one JSON object per message and line, e.g. for `jq`
+    #[clap(long)]
+    pub json: bool,
+}
+
+/// A comparison of a field with a value, e.g. `alt>1000`
+#[derive(Debug, Clone)]
+pub struct Condition {
+    field: String,
+    operator: Op,
+    value: String,
+}
+
+/// Print every received message passing the filters of `options` to stdout, until the output
+/// is closed
+pub async fn run(conn: &MavlinkConnectionHandler, options: &Options) -> io::Result<()> {
+    options.validate()?;
+    let mut frames = conn.tap().await;
+    let stdout = io::stdout();
+    while let Some(frame) = frames.next().await {
+        if !options.matches(&frame) {
+            continue;
+        }
+        match writeln!(stdout.lock(), "{}", options.format(&frame)) {
+            // e.g. piped into `head`
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+impl Options {
+    /// Check that all message names are known
+    pub fn validate(&self) -> io::Result<()> {
+        for name in &self.messages {
+            MavMessage::message_id_from_name(&name.to_uppercase()).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is not a known MAVLink message", name),
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Whether a frame passes all filters
+    pub fn matches(&self, frame: &Frame) -> bool {
+        let header = &frame.header;
+        if self.sysid.is_some_and(|id| id != header.system_id)
+            || self.compid.is_some_and(|id| id != header.component_id)
+        {
+            return false;
+        }
+        if !self.messages.is_empty() {
+            let name = fields::message_name(&frame.message);
+            if !self.messages.iter().any(|m| m.eq_ignore_ascii_case(&name)) {
+                return false;
+            }
+        }
+        if self.conditions.is_empty() {
+            return true;
+        }
+        let fields = fields::fields(&frame.message);
+        self.conditions.iter().all(|c| c.matches(&fields))
+    }
+
+    /// A frame as line of text or JSON
+    pub fn format(&self, frame: &Frame) -> String {
+        let time: DateTime<Local> = frame.time.into();
+        let header = &frame.header;
+        let name = fields::message_name(&frame.message);
+        let fields = fields::fields(&frame.message);
+
+        if self.json {
+            return to_json(frame, fields).to_string();
+        }
+
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|(field, value)| format!("{}={}", style(field).dim(), fields::to_text(value)))
+            .collect();
+        format!(
+            "{} {:>3}:{:<3} #{:<3} {} {}",
+            time.format("%H:%M:%S%.3f"),
+            header.system_id,
+            header.component_id,
+            header.sequence,
+            style(name).bold(),
+            fields.join(" ")
+        )
+    }
+}
+
+/// A frame as JSON object with its header, time of arrival and the given fields
+pub fn to_json(frame: &Frame, fields: Map<String, Value>) -> Value {
+    let time: DateTime<Local> = frame.time.into();
+    let header = &frame.header;
+    json!({
+        "time": time.to_rfc3339_opts(SecondsFormat::Millis, false),
+        "sysid": header.system_id,
+        "compid": header.component_id,
+        "seq": header.sequence,
+        "message": fields::message_name(&frame.message),
+        "fields": fields,
+    })
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (position, token, operator) = Op::SYMBOLS
+            .iter()
+            .filter_map(|(token, operator)| s.find(token).map(|p| (p, *token, *operator)))
+            .min_by_key(|(position, token, _)| (*position, std::cmp::Reverse(token.len())))
+            .ok_or_else(|| format!("{:?} is not of the form FIELD<operator>VALUE", s))?;
+
+        let field = s[..position].trim();
+        let value = s[position + token.len()..].trim();
+        if field.is_empty() || value.is_empty() {
+            return Err(format!("{:?} is not of the form FIELD<operator>VALUE", s));
+        }
+        Ok(Condition {
+            field: field.to_string(),
+            operator,
+            value: value.to_string(),
+        })
+    }
+}
+
+impl Condition {
+    /// Whether the fields of a message fulfill this condition
+    ///
+    /// Numbers are compared numerically, all other values by their text. Enum values may be
+    /// given without their prefix, e.g. `3D_FIX` for `GPS_FIX_TYPE_3D_FIX`. Messages without the
+    /// field never match.
+    pub fn matches(&self, fields: &Map<String, Value>) -> bool {
+        let actual = match fields.get(&self.field) {
+            Some(actual) => actual,
+            None => return false,
+        };
+
+        if let (Some(actual), Ok(expected)) = (actual.as_f64(), self.value.parse::<f64>()) {
+            return self.operator.apply(actual, expected);
+        }
+
+        let actual = fields::to_text(actual).to_uppercase();
+        let expected = self.value.to_uppercase();
+        let equal = actual == expected || actual.ends_with(&format!("_{}", expected));
+        match self.operator {
+            Op::Eq => equal,
+            Op::Ne => !equal,
+            _ => false,
+        }
+    }
+}
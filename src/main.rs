@@ -3,13 +3,35 @@ use std::sync::Arc;
 
 use clap::Clap;
 
+mod command;
 mod definitions;
+mod export;
+mod fields;
+mod get_set;
+mod groups;
+mod history;
+mod lint;
+mod listen;
 mod mavlink_stub;
+mod monitor;
 mod parameters;
+mod plot;
 mod push_pull;
+mod recent;
+mod replay;
+mod report;
+mod rules;
 mod skim;
+mod staging;
+mod stats;
+mod statustext;
+mod store;
+mod stream;
+mod tlog;
+mod tui;
 mod ui;
 mod util;
+mod vehicle;
 
 /// A tool to interact with MAVLink compatible vehicles.
 ///
@@ -28,6 +50,25 @@ pub struct Opts {
     )]
     mavlink_connection: String,
 
+    /// JSON file with additional consistency rules for parameters, checked by `configure`, `push`
+    /// and `lint`
+    #[clap(long)]
+    rules: Option<std::path::PathBuf>,
+
+    /// Print a summary of all parameter changes when `configure`, `push` or `set` exits
+    #[clap(long)]
+    report: bool,
+
+    /// Write all parameter changes of `configure`, `push` or `set` to a file, as JSON if it ends
+    /// in `.json`, as Markdown otherwise
+    #[clap(long)]
+    changelog: Option<std::path::PathBuf>,
+
+    /// Record every received and sent MAVLink frame to a telemetry log (tlog) file, which can be
+    /// opened in other ground station tools
+    #[clap(long)]
+    record: Option<std::path::PathBuf>,
+
     #[clap(subcommand)]
     cmd: SubCommand,
 }
@@ -36,21 +77,133 @@ pub struct Opts {
 pub enum SubCommand {
     /// Interactive configuration management
     ///
-    /// Starts a fuzzy finder which allows to search through the MAVLink parameters available on
-    /// the connected vehicle. Select one ([Return]) or multiple ([Tabulator]) parameters which you
-    /// would like to inspect. You can modify them, including sanity checking if metainformation is
-    /// avaibable on the parameter.
-    Configure,
+    /// Starts a full-screen interface which allows to search through the MAVLink parameters
+    /// available on the connected vehicle by typing. The selected parameter's definition and
+    /// current value are shown on the right, [Return] edits it. Edits are collected until they are
+    /// committed to the vehicle with [CTRL+S].
+    Configure {
+        /// Browse the parameters grouped by their library group and name prefixes
+        #[clap(short, long)]
+        grouped: bool,
+
+        /// Collect edits locally instead of pushing them immediately. Press [Escape] to review
+        /// the staged edits and commit them to the vehicle or discard them. The full-screen
+        /// interface always collects edits until they are committed.
+        #[clap(short, long)]
+        staged: bool,
+
+        /// Use the fuzzy finder and prompts instead of the full-screen interface
+        #[clap(long)]
+        classic: bool,
+
+        /// Show the status texts of the vehicle, e.g. pre-arm failures, below the parameters.
+        /// Not available with --classic.
+        #[clap(short, long)]
+        messages: bool,
+    },
     /// Pull configuration from the vehicle to a file
+    ///
+    /// With --from-log, the configuration is reconstructed from the parameters exchanged in a
+    /// telemetry log (tlog) instead, no vehicle is needed.
     Pull {
         #[clap()]
         out_file: std::path::PathBuf,
+        /// Telemetry log to take the last value of each parameter from
+        #[clap(long)]
+        from_log: Option<std::path::PathBuf>,
+        /// With --from-log, the system whose parameters to take
+        #[clap(long, requires = "from-log")]
+        sysid: Option<u8>,
+        /// With --from-log, the component whose parameters to take
+        #[clap(long, requires = "from-log")]
+        compid: Option<u8>,
     },
     /// Push configuration from a file to the vehicle
     Push {
         #[clap()]
         in_file: std::path::PathBuf,
     },
+    /// Print parameters of the vehicle
+    ///
+    /// Names may contain the wildcards `*` and `?`, e.g. `SERVO?_*`. The output is in the format
+    /// of `pull`. Exits with a non-zero status if a parameter was not found.
+    Get {
+        #[clap(required = true)]
+        names: Vec<String>,
+        /// Print the parameters as JSON, including their type and documented meaning
+        #[clap(long)]
+        json: bool,
+    },
+    /// Set parameters of the vehicle
+    ///
+    /// Each assignment is of the form NAME=VALUE. Documented values may be given by name, e.g.
+    /// `SERIAL2_PROTOCOL=MAVLink2`, bitmasks as a list of bit names, e.g. `LOG_BITMASK=IMU,GPS`.
+    /// Every change is read back from the vehicle. Exits with a non-zero status if any assignment
+    /// failed or was not confirmed.
+    Set {
+        #[clap(required = true)]
+        assignments: Vec<String>,
+        /// Print the results as JSON
+        #[clap(long)]
+        json: bool,
+        /// Set values which violate the parameter's definition anyway
+        #[clap(short, long)]
+        force: bool,
+    },
+    /// Show a live dashboard of the vehicle's telemetry
+    ///
+    /// Shows flight mode and armed state, attitude, position, GPS, HUD and battery state, as far
+    /// as the vehicle sends the respective messages. Values which are no longer updated are
+    /// dimmed. Press [q] or [Escape] to quit.
+    Monitor {
+        /// Comma separated list of the panels to show, in this order
+        #[clap(long, use_delimiter = true, default_value = monitor::PANELS)]
+        panels: Vec<monitor::Panel>,
+    },
+    /// Print the messages received from the vehicle
+    ///
+    /// Prints every message with the time it was received, its header (system id, component id
+    /// and sequence number) and its decoded fields, until interrupted with [CTRL+C].
+    Listen(listen::Options),
+    /// Plot numeric fields of the received messages
+    ///
+    /// Draws a scrolling line chart per field, e.g. `plot ATTITUDE.roll VFR_HUD.alt`, scaled to
+    /// the values in the window. Press [q] or [Escape] to quit.
+    Plot(plot::Options),
+    /// Show live statistics about the received messages
+    ///
+    /// Shows per system and component how many messages were received and lost, judging by gaps
+    /// in the sequence numbers, and per message type the rate and the bandwidth it takes up.
+    /// Frames which could not be decoded are counted as well, though live links already drop
+    /// frames with a bad checksum, which then only show as lost. Useful to verify stream rates and
+    /// to diagnose radio links. Press [q] or [Escape] to quit.
+    Stats,
+    /// Print the status texts of the vehicle
+    ///
+    /// Autopilots report warnings, errors and e.g. pre-arm failures as STATUSTEXT messages. These
+    /// are printed coloured by severity until interrupted with [CTRL+C]. Texts split into chunks
+    /// are joined again.
+    Messages(statustext::Options),
+    /// Export the messages of a telemetry log (tlog) for analysis
+    ///
+    /// Writes a CSV file per message type with a column per field, or with --json a JSON object
+    /// per message and line to stdout. The messages may be filtered like with `listen`.
+    Export(export::Options),
+    /// Replay a telemetry log (tlog) in real time
+    ///
+    /// The log is shown in one of the views of the subcommands of the same names, e.g. `replay
+    /// flight.tlog monitor`, as if the vehicle was connected. The playback can be accelerated and
+    /// stepped through message by message.
+    Replay(replay::Options),
+    /// Set or query the rate at which the vehicle sends messages
+    ///
+    /// Uses message intervals, which are acknowledged by the vehicle. Exits with a non-zero
+    /// status if any rate was rejected or could not be queried. Rates set this way are not
+    /// persistent, the vehicle returns to the rates of its `SRn_*` parameters after a reboot.
+    Stream {
+        #[clap(subcommand)]
+        cmd: StreamCommand,
+    },
     /// Browse all parameters with available metainformation
     ///
     /// Starts a fuzzy finder which allow to search through the MAVLink paramters for which
@@ -63,9 +216,86 @@ pub enum SubCommand {
         search_term: Option<String>,
         #[clap()]
         width: Option<usize>,
+        /// Browse the parameters grouped by their library group and name prefixes
+        #[clap(short, long)]
+        grouped: bool,
+    },
+    /// Check a parameter file without a vehicle
+    ///
+    /// Reports unknown and duplicate parameters, values outside of the documented range, invalid
+    /// enum values, undocumented bitmask bits, read only parameters and parameters which are not
+    /// available on the given vehicle. Exits with a non-zero status if any error was found.
+    Lint {
+        #[clap()]
+        file: std::path::PathBuf,
+        /// Vehicle the file is meant for, e.g. ArduCopter
+        #[clap(long)]
+        vehicle: Option<String>,
+        /// Firmware version the file is meant for, selects the cached metainformation
+        #[clap(long, requires = "vehicle")]
+        firmware: Option<String>,
+        /// Print the report as JSON to stdout
+        #[clap(long)]
+        json: bool,
+        /// Treat warnings as errors
+        #[clap(short = 'D', long)]
+        deny_warnings: bool,
+    },
+    /// Manage the cache of firmware specific parameter metainformation
+    ///
+    /// Parameters change between firmware releases. When connecting to a vehicle, the
+    /// metainformation matching the firmware reported in AUTOPILOT_VERSION is taken from this
+    /// cache. If there is none, the metainformation shipped with this binary is used.
+    Cache {
+        #[clap(subcommand)]
+        cmd: CacheCommand,
+    },
+}
+
+#[derive(Clap)]
+pub enum StreamCommand {
+    /// Set the rate of messages
+    ///
+    /// Each assignment is of the form NAME=RATE, where RATE is in Hz, `off` or `default`, e.g.
+    /// `ATTITUDE=20 GPS_RAW_INT=off`. If the vehicle does not support message intervals, the
+    /// ArduPilot data stream containing the message is requested instead, which changes the rate
+    /// of the other messages in the stream as well.
+    Set {
+        #[clap(required = true)]
+        assignments: Vec<String>,
+        /// Request data streams with REQUEST_DATA_STREAM instead, for firmware without support
+        /// for message intervals. NAME is a data stream then, e.g. `EXTRA1=10`. These requests
+        /// are not acknowledged.
+        #[clap(long)]
+        legacy: bool,
+    },
+    /// Print the current rate of messages
+    Get {
+        #[clap(required = true)]
+        names: Vec<String>,
     },
 }
 
+#[derive(Clap)]
+pub enum CacheCommand {
+    /// Import a parameter metainformation file (apm.pdef.json) into the cache
+    Import {
+        #[clap()]
+        file: std::path::PathBuf,
+        /// Autopilot the file belongs to
+        #[clap(long, default_value = "ArduPilot")]
+        autopilot: String,
+        /// Vehicle type the file belongs to, e.g. ArduCopter, ArduPlane, Rover
+        #[clap(long)]
+        vehicle: String,
+        /// Firmware version the file belongs to, e.g. 4.0.5
+        #[clap(long)]
+        version: String,
+    },
+    /// List the contents of the cache
+    List,
+}
+
 fn main() -> std::io::Result<()> {
     let opts: Opts = Opts::parse();
 
@@ -75,7 +305,9 @@ fn main() -> std::io::Result<()> {
 
     // without async
     match opts.cmd {
-        SubCommand::Info { search_term, width } if search_term.is_some() => {
+        SubCommand::Info {
+            search_term, width, ..
+        } if search_term.is_some() => {
             if let Some(search_term) = search_term {
                 let progress = ui::spinner("looking up message");
                 match definitions::lookup(&search_term) {
@@ -88,20 +320,111 @@ fn main() -> std::io::Result<()> {
             }
             return Ok(());
         }
-        SubCommand::Info { width, .. } => {
-            // for as long as the user wants
-            for def in skim::select(&definitions::all())? {
+        SubCommand::Info { width, grouped, .. } => {
+            let definitions = definitions::all();
+            let selection = match grouped {
+                true => skim::select_grouped(|| definitions.clone(), || None, None, &[], None)
+                    .map(|s| s.items),
+                false => skim::select(&definitions, None),
+            };
+            let selection = match selection {
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => Vec::new(),
+                selection => selection?,
+            };
+            for def in selection {
                 println!("{}", def.description(width.unwrap_or(default_width)));
             }
             return Ok(());
         }
+        SubCommand::Lint {
+            file,
+            vehicle,
+            firmware,
+            json,
+            deny_warnings,
+        } => {
+            if let (Some(vehicle), Some(version)) = (&vehicle, firmware) {
+                let firmware = vehicle::Firmware {
+                    autopilot: String::from("ArduPilot"),
+                    vehicle: vehicle.clone(),
+                    version,
+                    id: None,
+                };
+                if let Err(e) = definitions::load(&firmware) {
+                    ui::warning(&format!("{}, using the embedded definitions", e));
+                }
+            }
+
+            let rules = rules::load(opts.rules.as_deref())?;
+            let report = lint::lint(&file, vehicle.as_deref(), &rules)?;
+            match json {
+                true => println!("{}", serde_json::to_string_pretty(&report)?),
+                false => report.print(),
+            }
+            if report.errors > 0 || (deny_warnings && report.warnings > 0) {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        SubCommand::Cache {
+            cmd:
+                CacheCommand::Import {
+                    file,
+                    autopilot,
+                    vehicle,
+                    version,
+                },
+        } => {
+            let firmware = vehicle::Firmware {
+                autopilot,
+                vehicle,
+                version,
+                id: None,
+            };
+            let path = ui::wait_and_notice("importing definitions", || {
+                definitions::cache::import(&file, &firmware)
+            })?;
+            println!("imported {} as {}", firmware, path.display());
+            return Ok(());
+        }
+        SubCommand::Pull {
+            ref out_file,
+            from_log: Some(ref log),
+            sysid,
+            compid,
+        } => {
+            push_pull::pull_from_log(log, out_file, (sysid, compid))?;
+            return Ok(());
+        }
+        SubCommand::Export(ref options) => {
+            export::export(options)?;
+            return Ok(());
+        }
+        SubCommand::Cache {
+            cmd: CacheCommand::List,
+        } => {
+            for firmware in definitions::cache::list()? {
+                println!("{}", firmware);
+            }
+            return Ok(());
+        }
         _ => {}
     }
 
     smol::block_on(async {
-        let conn = Arc::new(mavlink_stub::MavlinkConnectionHandler::new(
-            &opts.mavlink_connection,
-        )?);
+        let playback = match &opts.cmd {
+            SubCommand::Replay(options) => Some(Arc::new(options.playback()?)),
+            _ => None,
+        };
+        let conn = Arc::new(match (&opts.cmd, &playback) {
+            (SubCommand::Replay(options), Some(playback)) => {
+                mavlink_stub::MavlinkConnectionHandler::replay(&options.file, playback.clone())?
+            }
+            _ => mavlink_stub::MavlinkConnectionHandler::new(&opts.mavlink_connection)?,
+        });
+        if let Some(path) = &opts.record {
+            conn.record(path)?;
+        }
 
         // spawn background worker
         smol::spawn({
@@ -110,31 +433,332 @@ fn main() -> std::io::Result<()> {
         })
         .detach();
 
-        match opts.cmd {
-            SubCommand::Pull { ref out_file } => {
-                push_pull::pull(&conn, &out_file).await.unwrap();
+        let mut session = report::Session::new();
+        let track = opts.report || opts.changelog.is_some();
+        let mut success = true;
+
+        // the changes made until a failure are still reported
+        let result = match opts.cmd {
+            SubCommand::Pull { ref out_file, .. } => {
+                push_pull::pull(&conn, out_file).await.unwrap();
+                return Ok(());
             }
             SubCommand::Push { ref in_file } => {
-                push_pull::push(&conn, &in_file).await.unwrap();
-            }
-            SubCommand::Configure => {
-                let mut parameters: Vec<_> = push_pull::fetch_parameters(&conn).await?;
-                loop {
-                    for mut param in skim::select(&parameters)? {
-                        param.mutate();
-                        param.push(&conn).await?;
-
-                        // TODO get rid of this uglyness
-                        for e in parameters.iter_mut() {
-                            if e.name == param.name {
-                                *e = param.clone();
+                let rules = rules::load(opts.rules.as_deref())?;
+                load_definitions(&conn).await;
+                let session = Some(&mut session).filter(|_| track);
+                push_pull::push(&conn, in_file, &rules, session).await
+            }
+            SubCommand::Get { ref names, json } => {
+                load_definitions(&conn).await;
+                if !get_set::get(&conn, names, json).await? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            SubCommand::Set {
+                ref assignments,
+                json,
+                force,
+            } => {
+                load_definitions(&conn).await;
+                get_set::set(&conn, assignments, json, force, &mut session)
+                    .await
+                    .map(|ok| success = ok)
+            }
+            SubCommand::Configure {
+                grouped,
+                staged,
+                classic,
+                messages,
+            } => {
+                let rules = rules::load(opts.rules.as_deref())?;
+                let firmware = load_definitions(&conn).await;
+                let mut recent = recent::Recent::load(firmware.as_ref());
+                let store = store::ParameterStore::fetch(conn.clone());
+                let result = match classic {
+                    true => {
+                        let options = (grouped, staged);
+                        configure(&conn, &store, &rules, options, &mut recent, &mut session).await
+                    }
+                    false => {
+                        let messages = messages.then(|| statustext::watch(conn.clone()));
+                        let messages = messages.as_deref();
+                        let recent = &mut recent;
+                        tui::run(
+                            &conn,
+                            &store,
+                            &rules,
+                            grouped,
+                            recent,
+                            messages,
+                            &mut session,
+                        )
+                        .await
+                    }
+                };
+                if let Err(e) = recent.save() {
+                    ui::warning(&format!("unable to remember the last search: {}", e));
+                }
+                result
+            }
+            SubCommand::Listen(ref options) => {
+                listen::run(&conn, options).await?;
+                return Ok(());
+            }
+            SubCommand::Messages(ref options) => {
+                statustext::run(&conn, options).await?;
+                return Ok(());
+            }
+            SubCommand::Replay(ref options) => {
+                if let Some(playback) = &playback {
+                    replay::run(conn.clone(), playback, options).await?;
+                }
+                return Ok(());
+            }
+            SubCommand::Plot(ref options) => {
+                plot::run(conn.clone(), options)?;
+                return Ok(());
+            }
+            SubCommand::Stats => {
+                stats::run(conn.clone())?;
+                return Ok(());
+            }
+            SubCommand::Stream {
+                cmd:
+                    StreamCommand::Set {
+                        ref assignments,
+                        legacy,
+                    },
+            } => {
+                if !stream::set(&conn, assignments, legacy).await? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            SubCommand::Stream {
+                cmd: StreamCommand::Get { ref names },
+            } => {
+                if !stream::get(&conn, names).await? {
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            SubCommand::Monitor { ref panels } => {
+                monitor::run(conn.clone(), panels)?;
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+
+        if opts.report {
+            session.print_summary();
+        }
+        if let Some(changelog) = &opts.changelog {
+            session.write(changelog)?;
+        }
+        result?;
+        if !success {
+            std::process::exit(1);
+        }
+        Ok(())
+    })
+}
+
+/// Interactive configuration management with the fuzzy finder
+///
+/// Each selected parameter is edited with a prompt, and pushed right away unless `staged`. The
+/// query, also one entered before leaving with [Escape], is kept between the fuzzy finder sessions
+/// and in `recent` for the next run, together with the last selected parameter.
+async fn configure(
+    conn: &mavlink_stub::MavlinkConnectionHandler,
+    store: &store::ParameterStore,
+    rules: &[rules::Rule],
+    (grouped, staged): (bool, bool),
+    recent: &mut recent::Recent,
+    session: &mut report::Session,
+) -> std::io::Result<()> {
+    let mut staging = staging::Staging::new();
+    let mut history = history::History::new();
+    let keys = [
+        history::UNDO_KEY,
+        history::REDO_KEY,
+        recent::KEY,
+        skim::ABORT,
+    ];
+    let mut only_recent = false;
+    loop {
+        let mut header: Vec<_> = rules::check(rules, &staging.overlay(store.all()))
+            .iter()
+            .map(|v| format!("{}: {}", console::style("warning").yellow().bold(), v))
+            .collect();
+        if !staging.is_empty() {
+            header.push(format!(
+                "{} staged changes, press [Escape] to review them",
+                console::style(staging.len()).bold()
+            ));
+        }
+        header.extend(history.hint());
+        let (received, count) = store.progress();
+        if grouped && (count == 0 || received < count) {
+            header.push(format!(
+                "received {} of {} parameters, the groups fill up while browsing",
+                received, count
+            ));
+        }
+        header.push(match only_recent {
+            true => format!(
+                "showing recently edited parameters, [{}] shows all",
+                recent::KEY
+            ),
+            false => format!("[{}] shows recently edited parameters", recent::KEY),
+        });
+        let header = Some(header.join("\n"));
+        let query = Some(recent.query.as_str()).filter(|q| !q.is_empty());
+
+        let items = match only_recent {
+            true => Some(store.items_named(recent.names())),
+            false if grouped => None,
+            false => Some(store.items()),
+        };
+        let selection = match items {
+            Some(items) => {
+                skim::select_from(items, header.as_deref(), &keys, query).map(|selection| {
+                    skim::Selection {
+                        items: staging.overlay(store.resolve(selection.items)),
+                        key: selection.key,
+                        query: selection.query,
+                    }
+                })
+            }
+            None => skim::select_grouped(
+                || staging.overlay(store.all()),
+                || (!store.is_complete()).then(|| store.revision()),
+                header.as_deref(),
+                &keys,
+                query,
+            ),
+        };
+        let selection = selection?;
+        recent.query = selection.query;
+        if let Some(param) = selection.items.last() {
+            recent.selected = Some(param.name.clone());
+        }
+
+        match selection.key.as_deref() {
+            Some(skim::ABORT) if staging.is_empty() => return Ok(()),
+            Some(skim::ABORT) => {
+                match staging.review()? {
+                    staging::Decision::Commit => {
+                        let mut failed = Vec::new();
+                        for (old, param) in staging.pending() {
+                            let (name, value) = (param.name.clone(), param.value);
+                            match apply(conn, store, param, old, recent, session).await {
+                                Ok(true) => {
+                                    staging.unstage(&name);
+                                    history.record(&name, old, value);
+                                }
+                                Ok(false) => failed.push(name),
+                                Err(e) => failed.push(format!("{} ({})", name, e)),
                             }
                         }
+                        if !failed.is_empty() {
+                            ui::warning(&format!(
+                                "{} changes stay staged: {}",
+                                failed.len(),
+                                failed.join(", ")
+                            ));
+                        }
                     }
+                    staging::Decision::Discard => drop(staging.take()),
+                    staging::Decision::Continue => {}
                 }
+                continue;
+            }
+            Some(recent::KEY) => {
+                only_recent = !only_recent;
+                continue;
             }
             _ => {}
+        }
+        let undo = selection.key.as_deref() == Some(history::UNDO_KEY);
+        let revert = match selection.key.as_deref() {
+            Some(history::UNDO_KEY) => history.undo().map(|e| (e.name.clone(), e.old)),
+            Some(history::REDO_KEY) => history.redo().map(|e| (e.name.clone(), e.new)),
+            _ => None,
         };
-        Ok(())
-    })
+        if let Some((name, value)) = revert {
+            let mut param = store
+                .get(&name)
+                .unwrap_or_else(|| parameters::Parameter::new(name, value));
+            let old = param.value;
+            param.value = value;
+            // the history only changes once the vehicle took the value
+            match apply(conn, store, param, old, recent, session).await? {
+                true if undo => history.undone(),
+                true => history.redone(),
+                false => {}
+            }
+        }
+
+        for mut param in selection.items {
+            let old = param.value;
+            param.mutate();
+            if staged {
+                let on_vehicle = store.get(&param.name).map_or(old, |p| p.value);
+                staging.stage(param, on_vehicle);
+            } else if param.value != old {
+                let (name, value) = (param.name.clone(), param.value);
+                if apply(conn, store, param, old, recent, session).await? {
+                    history.record(&name, old, value);
+                }
+            }
+        }
+    }
+}
+
+/// Push a changed parameter to the vehicle and record the change in the session
+///
+/// Returns whether the vehicle confirmed the change.
+async fn apply(
+    conn: &mavlink_stub::MavlinkConnectionHandler,
+    store: &store::ParameterStore,
+    param: parameters::Parameter,
+    old: f32,
+    recent: &mut recent::Recent,
+    session: &mut report::Session,
+) -> std::io::Result<bool> {
+    let (name, value) = (param.name.clone(), param.value);
+    recent.edited(&name);
+    let confirmed = store.push(conn, param, old, session).await?;
+    if !confirmed {
+        ui::warning(&format!(
+            "{} = {} was not confirmed by the vehicle",
+            name, value
+        ));
+    }
+    Ok(confirmed)
+}
+
+/// Use the cached definitions matching the vehicle's firmware, if available
+///
+/// Returns the firmware of the vehicle, if it could be identified.
+async fn load_definitions(
+    conn: &mavlink_stub::MavlinkConnectionHandler,
+) -> Option<vehicle::Firmware> {
+    let progress = ui::spinner("identifying vehicle");
+    let (firmware, result) = match vehicle::firmware(conn).await {
+        Ok(firmware) => {
+            progress.set_message(&format!("loading definitions for {}", firmware));
+            let result = definitions::load(&firmware);
+            (Some(firmware), result)
+        }
+        Err(e) => (None, Err(e)),
+    };
+    progress.finish();
+
+    if let Err(e) = result {
+        ui::warning(&format!("{}, using the embedded definitions", e));
+    }
+    firmware
 }
@@ -1,11 +1,13 @@
 use std::collections::HashMap;
 use std::io;
 pub use std::mem::{discriminant, Discriminant};
+use std::path::Path;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
-use mavlink::{common::*, MavConnection, MavHeader};
+use mavlink::{common::*, error::MessageReadError, MavConnection, MavHeader};
 
 use futures::{future::Either, prelude::*};
 use smol::{
@@ -13,11 +15,26 @@ use smol::{
     lock::Mutex,
 };
 
+use crate::{replay, tlog, ui};
+
 /// Returns the `MavMessageType` of a `MavMessage`
 pub use discriminant as message_type;
 
 pub type MavMessageType = Discriminant<MavMessage>;
 
+/// A received message together with its header and time of arrival
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub header: MavHeader,
+    pub message: MavMessage,
+    pub time: SystemTime,
+    /// The frame as it was received, if known
+    ///
+    /// The links of the mavlink crate only hand out the parsed message, so this is only known
+    /// for replayed logs. It is needed for fields the parsed message lacks, e.g. extensions.
+    pub raw: Option<Arc<[u8]>>,
+}
+
 /// A async adapter for a MAVLink connection
 ///
 /// Offers high level functionality to interact with a MAVLink vehicle in an async fashion.
@@ -26,7 +43,15 @@ pub struct MavlinkConnectionHandler {
     subscriptions: Mutex<HashMap<MavMessageType, Vec<Sender<MavMessage>>>>,
     tx: Sender<(MavMessageType, Sender<MavMessage>)>,
     rx: Receiver<(MavMessageType, Sender<MavMessage>)>,
+    tap_tx: Sender<Sender<Frame>>,
+    tap_rx: Receiver<Sender<Frame>>,
     last_heartbeat: Mutex<Option<Instant>>,
+    parse_errors: AtomicUsize,
+    record: std::sync::Mutex<Option<tlog::Writer>>,
+    /// Sequence number of the next frame sent, as the connection assigns it
+    sequence: AtomicU8,
+    /// Paces the frames when replaying a log, which also dictates their time
+    playback: Option<Arc<replay::Playback>>,
 }
 
 // TODO make this failable if no heartbeat is received
@@ -36,7 +61,7 @@ impl MavlinkConnectionHandler {
     /// # Arguments
     ///
     /// * `address` - MAVLink connection `&str`. Equivalent to the `address` argument in
-    /// [mavlink::connect](https://docs.rs/mavlink/*/mavlink/fn.connect.html)
+    ///   [mavlink::connect](https://docs.rs/mavlink/*/mavlink/fn.connect.html)
     ///
     /// # Examples
     ///
@@ -49,17 +74,33 @@ impl MavlinkConnectionHandler {
     pub fn new(address: &str) -> io::Result<Self> {
         let mut conn = mavlink::connect::<MavMessage>(address)?;
         conn.set_protocol_version(mavlink::MavlinkVersion::V1);
-        let conn = Arc::from(conn);
-        let (tx, rx) = channel::unbounded();
-        let subscriptions = Mutex::new(HashMap::new());
-        let last_heartbeat = Mutex::new(None);
-        Ok(Self {
-            conn,
-            subscriptions,
-            tx,
-            rx,
-            last_heartbeat,
-        })
+        Ok(Self::with_connection(Arc::from(conn), None))
+    }
+
+    /// Construct a MavlinkConnectionHandler replaying a telemetry log
+    ///
+    /// The frames of the log are received at the pace set by `playback`, with the time they were
+    /// recorded. Sent messages are discarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the tlog file to replay
+    /// * `playback` - controls the pace of the replay
+    pub fn replay(path: &Path, playback: Arc<replay::Playback>) -> io::Result<Self> {
+        let conn = replay::Connection::open(path, playback.clone())?;
+        Ok(Self::with_connection(Arc::new(conn), Some(playback)))
+    }
+
+    /// Record every received and sent frame to a tlog file
+    ///
+    /// Frames which can not be parsed are not recorded, see `tlog::Writer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the tlog file to write, an existing file is replaced
+    pub fn record(&self, path: &Path) -> io::Result<()> {
+        *self.record.lock().unwrap() = Some(tlog::Writer::create(path)?);
+        Ok(())
     }
 
     /// Says whethe
@@ -97,7 +138,6 @@ impl MavlinkConnectionHandler {
     ///     }
     /// }
     /// ```
-
     pub async fn subscribe(
         &self,
         message_type: MavMessageType,
@@ -107,6 +147,26 @@ impl MavlinkConnectionHandler {
         Box::pin(rx)
     }
 
+    /// Subscribe to all new MavMessages, regardless of their type
+    ///
+    /// Unlike `subscribe`, this yields the header and the time of arrival of each message as
+    /// well. This returns a never-ending Stream of Frames.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut stream = conn.tap().await;
+    ///
+    /// while let Some(frame) = stream.next().await {
+    ///     println!("{} {:?}", frame.header.system_id, frame.message);
+    /// }
+    /// ```
+    pub async fn tap(&self) -> Pin<Box<dyn Stream<Item = Frame>>> {
+        let (tx, rx) = channel::unbounded();
+        self.tap_tx.send(tx).await.unwrap(); // this may never fail
+        Box::pin(rx)
+    }
+
     /// Awaits the next MavMessage of the given MavMessageType
     ///
     /// # Arguments
@@ -122,7 +182,6 @@ impl MavlinkConnectionHandler {
     ///     // do something with `data`
     /// }
     /// ```
-
     pub async fn request(&self, message_type: MavMessageType) -> MavMessage {
         let (tx, rx) = channel::unbounded();
         self.tx.send((message_type, tx)).await.unwrap(); //this may never fail
@@ -148,7 +207,15 @@ impl MavlinkConnectionHandler {
     /// conn.send(&header, &message)?;
     /// ```
     pub fn send(&self, header: &MavHeader, message: &MavMessage) -> io::Result<()> {
-        self.conn.send(header, message)
+        // the connection replaces the sequence number by its own count
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        self.conn.send(header, message)?;
+        let header = MavHeader {
+            sequence,
+            ..*header
+        };
+        self.write_record(SystemTime::now(), header, message, None);
+        Ok(())
     }
 
     /// Send a `MavMessage` to the vehicle
@@ -168,38 +235,70 @@ impl MavlinkConnectionHandler {
     /// conn.send_default(&message)?;
     /// ```
     pub fn send_default(&self, message: &MavMessage) -> io::Result<()> {
-        self.conn.send_default(message)
+        self.send(&MavHeader::default(), message)
     }
 
     /// Returns the `Instant` from the last received HEARTBEAT
-    async fn last_heartbeat(&self) -> Option<Instant> {
+    pub async fn last_heartbeat(&self) -> Option<Instant> {
         let time = self.last_heartbeat.lock().await;
         *time
     }
 
+    /// Returns the number of received frames which could not be decoded
+    ///
+    /// These are e.g. of a type unknown to this program, or corrupted frames read from a log. Live
+    /// links drop frames with a bad checksum before they arrive here, those only show as gaps in
+    /// the sequence numbers.
+    pub fn parse_errors(&self) -> usize {
+        self.parse_errors.load(Ordering::Relaxed)
+    }
+
     /// Starts the eventloop of MavlinkConnectionHandler
     ///
     /// May only be called once, will block on subsequent calls.
     /// Must be called in order for the MavlinkConnectionHandler to work.
     pub async fn main_loop(&self) -> ! {
         let mut map = self.subscriptions.lock().await;
+        let mut taps: Vec<Sender<Frame>> = Vec::new();
 
-        let operations = self.rx.clone().map(Either::Left);
-        let messages = smol::stream::repeat_with(|| self.conn.recv()).map(Either::Right);
-        let mut combined = stream::select(operations, messages);
+        let operations = stream::select(
+            self.rx.clone().map(Either::Left),
+            self.tap_rx.clone().map(Either::Right),
+        );
+        let messages = smol::stream::repeat_with(|| self.conn.recv());
+        let mut combined =
+            stream::select(operations.map(Either::Left), messages.map(Either::Right));
 
         loop {
             match combined.next().await.unwrap() {
-                Either::Left((message_type, backchannel)) => {
+                Either::Left(Either::Left((message_type, backchannel))) => {
                     let subs = map
                         .entry(message_type)
                         .or_insert_with(|| Vec::with_capacity(1));
                     subs.push(backchannel);
                 }
-                Either::Right(Ok((_, MavMessage::HEARTBEAT(_)))) => {
-                    *self.last_heartbeat.lock().await = Some(Instant::now());
-                }
-                Either::Right(Ok((_header, msg))) => {
+                Either::Left(Either::Right(tap)) => taps.push(tap),
+                Either::Right(Ok((header, msg))) => {
+                    let (time, raw) = match &self.playback {
+                        Some(playback) => (
+                            playback.time().unwrap_or_else(SystemTime::now),
+                            playback.raw(),
+                        ),
+                        None => (SystemTime::now(), None),
+                    };
+                    self.write_record(time, header, &msg, raw.as_deref());
+                    if let MavMessage::HEARTBEAT(_) = msg {
+                        *self.last_heartbeat.lock().await = Some(Instant::now());
+                    }
+                    if !taps.is_empty() {
+                        let frame = Frame {
+                            header,
+                            message: msg.clone(),
+                            time,
+                            raw,
+                        };
+                        taps.retain(|tap| tap.try_send(frame.clone()).is_ok());
+                    }
                     map.entry(discriminant(&msg))
                         .or_insert_with(Vec::new)
                         .retain(|backchannel| match backchannel.is_closed() {
@@ -211,8 +310,59 @@ impl MavlinkConnectionHandler {
                             }
                         });
                 }
+                Either::Right(Err(MessageReadError::Parse(_))) => {
+                    self.parse_errors.fetch_add(1, Ordering::Relaxed);
+                }
+                Either::Right(Err(MessageReadError::Io(e)))
+                    if e.kind() == io::ErrorKind::InvalidData =>
+                {
+                    self.parse_errors.fetch_add(1, Ordering::Relaxed);
+                }
                 _ => {}
             }
         }
     }
+
+    fn with_connection(
+        conn: Arc<dyn MavConnection<MavMessage> + Sync + Send>,
+        playback: Option<Arc<replay::Playback>>,
+    ) -> Self {
+        let (tx, rx) = channel::unbounded();
+        let (tap_tx, tap_rx) = channel::unbounded();
+        let subscriptions = Mutex::new(HashMap::new());
+        let last_heartbeat = Mutex::new(None);
+        Self {
+            conn,
+            subscriptions,
+            tx,
+            rx,
+            tap_tx,
+            tap_rx,
+            last_heartbeat,
+            parse_errors: AtomicUsize::new(0),
+            record: std::sync::Mutex::new(None),
+            sequence: AtomicU8::new(0),
+            playback,
+        }
+    }
+
+    /// Append a frame to the tlog, if recording
+    ///
+    /// Recording stops with a warning if the tlog can not be written, the connection itself
+    /// keeps working.
+    fn write_record(
+        &self,
+        time: SystemTime,
+        header: MavHeader,
+        message: &MavMessage,
+        raw: Option<&[u8]>,
+    ) {
+        let mut record = self.record.lock().unwrap();
+        if let Some(writer) = record.as_mut() {
+            if let Err(e) = writer.write(time, header, message, raw) {
+                ui::warning(&format!("stopped recording: {}", e));
+                *record = None;
+            }
+        }
+    }
 }
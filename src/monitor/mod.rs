@@ -0,0 +1,412 @@
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::prelude::*;
+use mavlink::common::*;
+use tuikit::prelude::*;
+
+use crate::{
+    mavlink_stub::{self, MavlinkConnectionHandler},
+    replay::{self, Playback},
+    tui::to_io,
+    util, vehicle,
+};
+
+mod telemetry;
+
+use telemetry::Sample;
+pub use telemetry::Telemetry;
+
+// API
+
+/// A part of the dashboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Panel {
+    /// Flight mode and armed state from HEARTBEAT
+    Status,
+    /// Roll, pitch and yaw from ATTITUDE
+    Attitude,
+    /// Position and velocity from GLOBAL_POSITION_INT
+    Position,
+    /// Fix and accuracy from GPS_RAW_INT
+    Gps,
+    /// Speeds, climb rate and throttle from VFR_HUD
+    Hud,
+    /// Voltage, current and charge from SYS_STATUS and BATTERY_STATUS
+    Battery,
+}
+
+/// All panels, in the order they are shown by default
+pub const PANELS: &str = "status,attitude,position,gps,hud,battery";
+
+/// Show a live dashboard of the vehicle's telemetry until [q] or [Escape] is pressed
+pub fn run(conn: Arc<MavlinkConnectionHandler>, panels: &[Panel]) -> io::Result<()> {
+    let telemetry = watch(conn);
+    show(&telemetry, panels, None)
+}
+
+/// Keep the telemetry up to date in the background
+pub fn watch(conn: Arc<MavlinkConnectionHandler>) -> Arc<Mutex<Telemetry>> {
+    let telemetry = Arc::new(Mutex::new(Telemetry::default()));
+    util::spawn({
+        let telemetry = telemetry.clone();
+        move || async move {
+            let mut subscriptions = Vec::new();
+            for message in telemetry::messages() {
+                let message_type = mavlink_stub::message_type(&message);
+                subscriptions.push(conn.subscribe(message_type).await);
+            }
+            let mut messages = stream::select_all(subscriptions);
+            while let Some(message) = messages.next().await {
+                telemetry.lock().unwrap().update(&message);
+            }
+        }
+    });
+    telemetry
+}
+
+/// Draw the given panels of the telemetry until [q] or [Escape] is pressed
+///
+/// When replaying a log, its `playback` is shown and controlled as well.
+pub fn show(
+    telemetry: &Mutex<Telemetry>,
+    panels: &[Panel],
+    playback: Option<&Playback>,
+) -> io::Result<()> {
+    let term: Term<()> = Term::new().map_err(to_io)?;
+    // the dashboard is only drawn again once the telemetry, its age or the playback changed
+    let mut drawn = None;
+    loop {
+        {
+            let telemetry = telemetry.lock().unwrap();
+            let state = (
+                telemetry.revision,
+                titles(panels, &telemetry),
+                playback.map(Playback::status),
+            );
+            if drawn.as_ref() != Some(&state) {
+                draw(&term, &telemetry, panels, playback).map_err(to_io)?;
+                drawn = Some(state);
+            }
+        }
+        match term.peek_event(REFRESH) {
+            Ok(Event::Key(Key::Char('q'))) | Ok(Event::Key(Key::ESC)) => break,
+            Ok(Event::Key(Key::Ctrl('c'))) => break,
+            Ok(Event::Key(key)) => {
+                if let Some(playback) = playback {
+                    playback.handle(&key);
+                }
+            }
+            Ok(Event::Resize { .. }) => drawn = None,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+impl FromStr for Panel {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "status" => Ok(Panel::Status),
+            "attitude" => Ok(Panel::Attitude),
+            "position" => Ok(Panel::Position),
+            "gps" => Ok(Panel::Gps),
+            "hud" => Ok(Panel::Hud),
+            "battery" => Ok(Panel::Battery),
+            other => Err(format!(
+                "{:?} is not a panel, choose from {}",
+                other, PANELS
+            )),
+        }
+    }
+}
+
+impl Display for Panel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Panel::Status => "status",
+            Panel::Attitude => "attitude",
+            Panel::Position => "position",
+            Panel::Gps => "gps",
+            Panel::Hud => "hud",
+            Panel::Battery => "battery",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Implementation details
+
+/// How often the dashboard is redrawn
+const REFRESH: Duration = Duration::from_millis(200);
+
+/// Values older than this are shown dimmed, as the vehicle stopped sending them
+const STALE: Duration = Duration::from_secs(2);
+
+/// A row of a panel: label, value and how to show the value
+type Row = (&'static str, String, Attr);
+
+fn draw(
+    term: &Term<()>,
+    telemetry: &Telemetry,
+    panels: &[Panel],
+    playback: Option<&Playback>,
+) -> tuikit::Result<()> {
+    let views: Vec<_> = panels
+        .iter()
+        .map(|panel| View {
+            rows: rows(*panel, telemetry),
+            waiting_for: panel.message(),
+        })
+        .collect();
+    let titles = titles(panels, telemetry);
+
+    let controls = playback.map(replay::Controls);
+    let mut root = VSplit::default();
+    for (views, titles) in views.chunks(2).zip(titles.chunks(2)) {
+        let mut row = HSplit::default();
+        for (view, title) in views.iter().zip(titles) {
+            row = row.split(Win::new(view).border(true).title(title.as_str()));
+        }
+        root = root.split(row);
+    }
+    if let Some(controls) = &controls {
+        root = root.split(Win::new(controls).basis(1).grow(0).shrink(0));
+    }
+
+    term.clear()?;
+    term.draw(&root)?;
+    term.present()
+}
+
+struct View {
+    rows: Option<Vec<Row>>,
+    /// The message this panel shows, to be named while there is none
+    waiting_for: &'static str,
+}
+
+impl Draw for View {
+    fn draw(&self, canvas: &mut dyn Canvas) -> tuikit::Result<()> {
+        let rows = match &self.rows {
+            Some(rows) => rows,
+            None => {
+                let text = format!("waiting for {}", self.waiting_for);
+                canvas.print_with_attr(0, 0, &text, Effect::DIM.into())?;
+                return Ok(());
+            }
+        };
+        for (row, (label, value, attr)) in rows.iter().enumerate() {
+            canvas.print_with_attr(row, 0, label, Color::CYAN.into())?;
+            canvas.print_with_attr(row, 12, value, *attr)?;
+        }
+        Ok(())
+    }
+}
+
+impl Widget for View {}
+
+impl Panel {
+    fn message(self) -> &'static str {
+        match self {
+            Panel::Status => "HEARTBEAT",
+            Panel::Attitude => "ATTITUDE",
+            Panel::Position => "GLOBAL_POSITION_INT",
+            Panel::Gps => "GPS_RAW_INT",
+            Panel::Hud => "VFR_HUD",
+            Panel::Battery => "SYS_STATUS",
+        }
+    }
+}
+
+/// Time since the message shown in a panel was received
+fn age(panel: Panel, telemetry: &Telemetry) -> Option<Duration> {
+    match panel {
+        Panel::Status => telemetry.heartbeat.as_ref().map(Sample::age),
+        Panel::Attitude => telemetry.attitude.as_ref().map(Sample::age),
+        Panel::Position => telemetry.position.as_ref().map(Sample::age),
+        Panel::Gps => telemetry.gps.as_ref().map(Sample::age),
+        Panel::Hud => telemetry.hud.as_ref().map(Sample::age),
+        Panel::Battery => telemetry.sys_status.as_ref().map(Sample::age),
+    }
+}
+
+/// The titles of the panels, which tell how long a panel was not updated
+fn titles(panels: &[Panel], telemetry: &Telemetry) -> Vec<String> {
+    panels
+        .iter()
+        .map(|panel| match age(*panel, telemetry) {
+            Some(age) if age > STALE => format!(" {} (no update for {}s) ", panel, age.as_secs()),
+            _ => format!(" {} ", panel),
+        })
+        .collect()
+}
+
+/// The rows of a panel, `None` if its message was not received yet
+fn rows(panel: Panel, telemetry: &Telemetry) -> Option<Vec<Row>> {
+    let attr = match age(panel, telemetry)? > STALE {
+        true => Attr::from(Effect::DIM),
+        false => Attr::default(),
+    };
+    let row = |label, value: String| (label, value, attr);
+
+    let rows = match panel {
+        Panel::Status => {
+            let data = &telemetry.heartbeat.as_ref()?.data;
+            let armed = data
+                .base_mode
+                .contains(MavModeFlag::MAV_MODE_FLAG_SAFETY_ARMED);
+            let armed = match armed {
+                true => ("armed", String::from("ARMED"), Color::RED.into()),
+                false => ("armed", String::from("disarmed"), Color::GREEN.into()),
+            };
+            vec![
+                row("mode", vehicle::mode_name(data)),
+                armed,
+                row("state", enum_name(data.system_status, "MAV_STATE_")),
+                row("type", enum_name(data.mavtype, "MAV_TYPE_")),
+                row("autopilot", enum_name(data.autopilot, "MAV_AUTOPILOT_")),
+            ]
+        }
+        Panel::Attitude => {
+            let data = &telemetry.attitude.as_ref()?.data;
+            let angle = |angle: f32, rate: f32| {
+                format!("{:7.1}°  {:7.1}°/s", angle.to_degrees(), rate.to_degrees())
+            };
+            vec![
+                row("roll", angle(data.roll, data.rollspeed)),
+                row("pitch", angle(data.pitch, data.pitchspeed)),
+                row("yaw", angle(data.yaw, data.yawspeed)),
+            ]
+        }
+        Panel::Position => {
+            let data = &telemetry.position.as_ref()?.data;
+            vec![
+                row("latitude", degrees(data.lat)),
+                row("longitude", degrees(data.lon)),
+                row("altitude", format!("{:.2} m MSL", data.alt as f32 / 1e3)),
+                row(
+                    "relative",
+                    format!("{:.2} m", data.relative_alt as f32 / 1e3),
+                ),
+                row(
+                    "velocity",
+                    format!(
+                        "N {:.2}  E {:.2}  D {:.2} m/s",
+                        data.vx as f32 / 100.0,
+                        data.vy as f32 / 100.0,
+                        data.vz as f32 / 100.0
+                    ),
+                ),
+                row("heading", centi(data.hdg, "°")),
+            ]
+        }
+        Panel::Gps => {
+            let data = &telemetry.gps.as_ref()?.data;
+            vec![
+                row("fix", enum_name(data.fix_type, "GPS_FIX_TYPE_")),
+                row("satellites", unknown_if(data.satellites_visible, u8::MAX)),
+                row("hdop", centi(data.eph, "")),
+                row("vdop", centi(data.epv, "")),
+                row("latitude", degrees(data.lat)),
+                row("longitude", degrees(data.lon)),
+                row("altitude", format!("{:.2} m MSL", data.alt as f32 / 1e3)),
+                row("speed", centi(data.vel, " m/s")),
+                row("course", centi(data.cog, "°")),
+            ]
+        }
+        Panel::Hud => {
+            let data = &telemetry.hud.as_ref()?.data;
+            vec![
+                row("airspeed", format!("{:.1} m/s", data.airspeed)),
+                row("groundspeed", format!("{:.1} m/s", data.groundspeed)),
+                row("altitude", format!("{:.1} m", data.alt)),
+                row("climb", format!("{:.1} m/s", data.climb)),
+                row("heading", format!("{}°", data.heading)),
+                row("throttle", format!("{}%", data.throttle)),
+            ]
+        }
+        Panel::Battery => {
+            let data = &telemetry.sys_status.as_ref()?.data;
+            let mut rows = vec![
+                row(
+                    "voltage",
+                    format!("{:.2} V", data.voltage_battery as f32 / 1e3),
+                ),
+                row(
+                    "current",
+                    match data.current_battery {
+                        -1 => String::from("unknown"),
+                        current => format!("{:.2} A", current as f32 / 100.0),
+                    },
+                ),
+                row(
+                    "remaining",
+                    match data.battery_remaining {
+                        -1 => String::from("unknown"),
+                        remaining => format!("{}%", remaining),
+                    },
+                ),
+            ];
+            if let Some(battery) = &telemetry.battery {
+                let battery = &battery.data;
+                let cells: Vec<_> = battery
+                    .voltages
+                    .iter()
+                    .filter(|v| **v != u16::MAX)
+                    .map(|v| format!("{:.2}", *v as f32 / 1e3))
+                    .collect();
+                if battery.current_consumed >= 0 {
+                    rows.push(row("consumed", format!("{} mAh", battery.current_consumed)));
+                }
+                if !cells.is_empty() {
+                    rows.push(row("cells", format!("{} V", cells.join(" "))));
+                }
+                if battery.temperature != i16::MAX {
+                    rows.push(row(
+                        "temperature",
+                        format!("{:.1} °C", battery.temperature as f32 / 100.0),
+                    ));
+                }
+            }
+            rows.push(row("cpu load", format!("{:.1}%", data.load as f32 / 10.0)));
+            rows.push(row(
+                "comm drops",
+                format!("{:.2}%", data.drop_rate_comm as f32 / 100.0),
+            ));
+            rows
+        }
+    };
+    Some(rows)
+}
+
+/// Degrees from the `degE7` representation
+fn degrees(value: i32) -> String {
+    format!("{:.7}°", value as f64 / 1e7)
+}
+
+/// A value sent in hundredths, `u16::MAX` stands for unknown
+fn centi(value: u16, unit: &str) -> String {
+    match value {
+        u16::MAX => String::from("unknown"),
+        value => format!("{:.2}{}", value as f32 / 100.0, unit),
+    }
+}
+
+fn unknown_if<T: PartialEq + Display>(value: T, unknown: T) -> String {
+    match value == unknown {
+        true => String::from("unknown"),
+        false => value.to_string(),
+    }
+}
+
+/// The name of a MAVLink enum value without its prefix, e.g. `ACTIVE` for `MAV_STATE_ACTIVE`
+fn enum_name(value: impl fmt::Debug, prefix: &str) -> String {
+    format!("{:?}", value)
+        .trim_start_matches(prefix)
+        .to_string()
+}
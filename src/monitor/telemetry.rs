@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+use mavlink::common::*;
+
+use crate::vehicle;
+
+/// A message and when it was received
+#[derive(Debug, Clone)]
+pub struct Sample<T> {
+    pub data: T,
+    pub received: Instant,
+}
+
+/// The latest telemetry of a vehicle
+#[derive(Debug, Default)]
+pub struct Telemetry {
+    pub heartbeat: Option<Sample<HEARTBEAT_DATA>>,
+    pub attitude: Option<Sample<ATTITUDE_DATA>>,
+    pub position: Option<Sample<GLOBAL_POSITION_INT_DATA>>,
+    pub sys_status: Option<Sample<SYS_STATUS_DATA>>,
+    pub battery: Option<Sample<BATTERY_STATUS_DATA>>,
+    pub gps: Option<Sample<GPS_RAW_INT_DATA>>,
+    pub hud: Option<Sample<VFR_HUD_DATA>>,
+    /// Counts the messages taken, to draw the dashboard only when it changed
+    pub revision: usize,
+}
+
+/// The messages `Telemetry` is made of
+pub fn messages() -> Vec<MavMessage> {
+    vec![
+        MavMessage::HEARTBEAT(Default::default()),
+        MavMessage::ATTITUDE(Default::default()),
+        MavMessage::GLOBAL_POSITION_INT(Default::default()),
+        MavMessage::SYS_STATUS(Default::default()),
+        MavMessage::BATTERY_STATUS(Default::default()),
+        MavMessage::GPS_RAW_INT(Default::default()),
+        MavMessage::VFR_HUD(Default::default()),
+    ]
+}
+
+impl Telemetry {
+    /// Take the contents of a received message
+    pub fn update(&mut self, message: &MavMessage) {
+        let received = Instant::now();
+        match message.clone() {
+            MavMessage::HEARTBEAT(data) if vehicle::is_autopilot(&data) => {
+                self.heartbeat = Some(Sample { data, received })
+            }
+            MavMessage::ATTITUDE(data) => self.attitude = Some(Sample { data, received }),
+            MavMessage::GLOBAL_POSITION_INT(data) => {
+                self.position = Some(Sample { data, received })
+            }
+            MavMessage::SYS_STATUS(data) => self.sys_status = Some(Sample { data, received }),
+            MavMessage::BATTERY_STATUS(data) => self.battery = Some(Sample { data, received }),
+            MavMessage::GPS_RAW_INT(data) => self.gps = Some(Sample { data, received }),
+            MavMessage::VFR_HUD(data) => self.hud = Some(Sample { data, received }),
+            _ => return,
+        }
+        self.revision += 1;
+    }
+}
+
+impl<T> Sample<T> {
+    pub fn age(&self) -> Duration {
+        self.received.elapsed()
+    }
+}
@@ -1,12 +1,15 @@
 use std::fmt::{self, Display, Formatter};
 use std::io;
+use std::time::Duration;
 
+use console::style;
+use futures::prelude::*;
 use mavlink::common::*;
 use skim::{prelude::*, DisplayContext, PreviewContext, SkimItem};
 
 use crate::{
     definitions::{self, Definition, User},
-    mavlink_stub::MavlinkConnectionHandler,
+    mavlink_stub::{message_type, MavMessageType, MavlinkConnectionHandler},
     util::*,
 };
 
@@ -20,9 +23,38 @@ use crate::{
 pub struct Parameter {
     pub name: String,
     pub value: f32,
+    /// The value as initially read from the vehicle in this session, if it was read from one
+    pub initial: Option<f32>,
+    /// The type of the parameter on the vehicle, `REAL32` if it is not known
+    pub param_type: MavParamType,
 }
 
 impl Parameter {
+    /// Create a parameter which was not read from a vehicle
+    pub fn new(name: String, value: f32) -> Self {
+        Parameter {
+            name,
+            value,
+            initial: None,
+            param_type: MavParamType::MAV_PARAM_TYPE_REAL32,
+        }
+    }
+
+    /// Create a parameter as read from the vehicle
+    pub fn from_vehicle(name: String, value: f32, param_type: MavParamType) -> Self {
+        Parameter {
+            name,
+            value,
+            initial: Some(value),
+            param_type,
+        }
+    }
+
+    /// Whether the value differs from the one initially read from the vehicle
+    pub fn is_changed(&self) -> bool {
+        self.initial.is_some_and(|initial| initial != self.value)
+    }
+
     /// Try to find a Definition for the Parameter.
     ///
     /// If not suitable Definition is found, this defaults to a sensible default.
@@ -37,7 +69,12 @@ impl Parameter {
                     display_name: unknown.clone(),
                     user: User::Advanced,
                     data: None,
+                    read_only: false,
+                    reboot_required: false,
+                    default: None,
+                    increment: None,
                     vehicle: unknown,
+                    vehicles: Vec::new(),
                 }
             }
         }
@@ -48,7 +85,58 @@ impl Parameter {
     /// This takes over control over the terminal, and thus may disrupt other output.
     pub fn mutate(&mut self) {
         let def = self.definition();
-        self.value = def.interact(self.value);
+        self.value = def.interact(self.value, self.param_type);
+    }
+
+    /// Read a single parameter from the vehicle
+    pub async fn fetch(conn: &MavlinkConnectionHandler, name: &str) -> io::Result<Self> {
+        let mut stream = conn.subscribe(param_value_type()).await;
+        let request = MavMessage::PARAM_REQUEST_READ(PARAM_REQUEST_READ_DATA {
+            param_index: -1,
+            target_system: 0,
+            target_component: 0,
+            param_id: to_char_arr(name),
+        });
+
+        for _ in 0..RETRIES {
+            conn.send_default(&request)?;
+            if let Ok(data) =
+                with_timeout(TIMEOUT, "PARAM_VALUE", next_value(&mut stream, name)).await
+            {
+                return Ok(Parameter::from_vehicle(
+                    name.to_string(),
+                    data.param_value,
+                    data.param_type,
+                ));
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("the vehicle did not report a value for {}", name),
+        ))
+    }
+
+    /// Push the value to the vehicle and wait for the vehicle to confirm it
+    ///
+    /// Returns whether the vehicle reported back the new value.
+    pub async fn push_confirmed(&self, conn: &MavlinkConnectionHandler) -> io::Result<bool> {
+        let mut stream = conn.subscribe(param_value_type()).await;
+
+        for _ in 0..RETRIES {
+            self.push(conn).await?;
+            if let Ok(data) =
+                with_timeout(TIMEOUT, "PARAM_VALUE", next_value(&mut stream, &self.name)).await
+            {
+                // integer parameters are reported back rounded, floats have to match exactly
+                let integer = !matches!(
+                    data.param_type,
+                    MavParamType::MAV_PARAM_TYPE_REAL32 | MavParamType::MAV_PARAM_TYPE_REAL64
+                );
+                let rounded = integer && data.param_value == self.value.round();
+                return Ok(data.param_value == self.value || rounded);
+            }
+        }
+        Ok(false)
     }
 
     pub async fn push(&self, conn: &MavlinkConnectionHandler) -> io::Result<()> {
@@ -57,7 +145,7 @@ impl Parameter {
             target_system: 0,
             target_component: 0,
             param_id: to_char_arr(&self.name),
-            param_type: Default::default(),
+            param_type: self.param_type,
         });
         conn.send_default(&message)?;
         Ok(())
@@ -65,6 +153,33 @@ impl Parameter {
 }
 
 // Implementation details
+
+/// How often a request is sent before giving up
+const RETRIES: usize = 3;
+
+/// How long to wait for an answer to a request
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+fn param_value_type() -> MavMessageType {
+    message_type(&MavMessage::PARAM_VALUE(Default::default()))
+}
+
+/// Await the next PARAM_VALUE for the parameter `name`
+async fn next_value<S>(stream: &mut S, name: &str) -> PARAM_VALUE_DATA
+where
+    S: Stream<Item = MavMessage> + Unpin,
+{
+    loop {
+        match stream.next().await {
+            Some(MavMessage::PARAM_VALUE(data)) if to_string(&data.param_id) == name => {
+                return data
+            }
+            Some(_) => {}
+            None => futures::future::pending().await,
+        }
+    }
+}
+
 impl Display for Parameter {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         self.definition().fmt(f)
@@ -72,11 +187,11 @@ impl Display for Parameter {
 }
 
 impl SkimItem for Parameter {
-    fn display(&self, _context: DisplayContext) -> AnsiString {
+    fn display(&self, _context: DisplayContext) -> AnsiString<'_> {
         AnsiString::parse(&self.definition().name())
     }
 
-    fn text(&self) -> Cow<str> {
+    fn text(&self) -> Cow<'_, str> {
         let def = self.definition();
         let all_text = format!(
             "{}\n{}\n{}\n{}",
@@ -87,6 +202,32 @@ impl SkimItem for Parameter {
 
     fn preview(&self, _context: PreviewContext) -> ItemPreview {
         let width = textwrap::termwidth() / 2 - 1;
-        ItemPreview::AnsiText(self.definition().description(width))
+        let def = self.definition();
+
+        let mut status = format!(
+            "{} {}",
+            style("current value:").bold(),
+            def.describe_value(self.value, Some(self.param_type))
+        );
+        if let Some(default) = def.default {
+            status += &format!(
+                "\n{} {}",
+                style("default:").bold(),
+                def.describe_value(default, Some(self.param_type))
+            );
+        }
+        if let Some(initial) = self.initial.filter(|_| self.is_changed()) {
+            status += &format!(
+                "\n{} {}",
+                style("changed this session, was:").yellow().bold(),
+                def.describe_value(initial, Some(self.param_type))
+            );
+        }
+
+        ItemPreview::AnsiText(format!(
+            "{}\n\n{}",
+            textwrap::fill(&status, width),
+            def.description(width)
+        ))
     }
 }
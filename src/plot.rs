@@ -0,0 +1,386 @@
+use std::collections::VecDeque;
+use std::io;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use clap::Clap;
+use futures::prelude::*;
+use mavlink::{common::MavMessage, Message};
+use tuikit::prelude::*;
+
+use crate::{
+    fields,
+    mavlink_stub::{Frame, MavlinkConnectionHandler},
+    replay::{self, Playback},
+    tui::to_io,
+    util,
+};
+
+// API
+
+/// Which fields to plot and how
+#[derive(Clap, Debug, Clone)]
+pub struct Options {
+    /// Numeric fields to plot, e.g. `ATTITUDE.roll VFR_HUD.alt`, each in a chart of its own
+    #[clap(required = true)]
+    pub series: Vec<Series>,
+
+    /// Seconds of history to show
+    #[clap(short, long, default_value = "30")]
+    pub window: f64,
+
+    /// Only plot messages of this system
+    #[clap(long)]
+    pub sysid: Option<u8>,
+
+    /// Only plot messages of this component
+    #[clap(long)]
+    pub compid: Option<u8>,
+}
+
+/// A field of a message, e.g. `ATTITUDE.roll`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Series {
+    message: String,
+    field: String,
+}
+
+/// The recent values of all series
+#[derive(Debug)]
+pub struct Plot {
+    window: Duration,
+    series: Vec<(Series, VecDeque<(SystemTime, f64)>)>,
+    sysid: Option<u8>,
+    compid: Option<u8>,
+    /// The time of the latest message, the right end of the charts
+    now: Option<SystemTime>,
+    /// Counts the changes of the series, to draw the charts only when they changed
+    revision: usize,
+}
+
+/// Plot fields of the received messages until [q] or [Escape] is pressed
+pub fn run(conn: Arc<MavlinkConnectionHandler>, options: &Options) -> io::Result<()> {
+    let plot = watch(conn, options)?;
+    show(&plot, None)
+}
+
+/// Collect the values of the series in the background
+pub fn watch(
+    conn: Arc<MavlinkConnectionHandler>,
+    options: &Options,
+) -> io::Result<Arc<Mutex<Plot>>> {
+    options.validate()?;
+    let plot = Arc::new(Mutex::new(Plot {
+        window: Duration::from_secs_f64(options.window),
+        series: options
+            .series
+            .iter()
+            .map(|series| (series.clone(), VecDeque::new()))
+            .collect(),
+        sysid: options.sysid,
+        compid: options.compid,
+        now: None,
+        revision: 0,
+    }));
+    util::spawn({
+        let plot = plot.clone();
+        move || async move {
+            let mut frames = conn.tap().await;
+            while let Some(frame) = frames.next().await {
+                plot.lock().unwrap().update(&frame);
+            }
+        }
+    });
+    Ok(plot)
+}
+
+/// Draw the charts until [q] or [Escape] is pressed
+///
+/// When replaying a log, its `playback` is shown and controlled as well.
+pub fn show(plot: &Mutex<Plot>, playback: Option<&Playback>) -> io::Result<()> {
+    let term: Term<()> = Term::new().map_err(to_io)?;
+    // the charts are only drawn again once the series or the playback changed
+    let mut drawn = None;
+    loop {
+        {
+            let plot = plot.lock().unwrap();
+            let state = (plot.revision, playback.map(Playback::status));
+            if drawn.as_ref() != Some(&state) {
+                draw(&term, &plot, playback).map_err(to_io)?;
+                drawn = Some(state);
+            }
+        }
+        match term.peek_event(REFRESH) {
+            Ok(Event::Key(Key::Char('q'))) | Ok(Event::Key(Key::ESC)) => break,
+            Ok(Event::Key(Key::Ctrl('c'))) => break,
+            Ok(Event::Key(key)) => {
+                if let Some(playback) = playback {
+                    playback.handle(&key);
+                }
+            }
+            Ok(Event::Resize { .. }) => drawn = None,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+impl Options {
+    /// Check that all series are numeric fields of known messages
+    pub fn validate(&self) -> io::Result<()> {
+        if !(self.window > 0.0 && self.window.is_finite()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the window has to be a positive number of seconds",
+            ));
+        }
+        for series in &self.series {
+            let invalid = |reason: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{}: {}", series, reason),
+                )
+            };
+            let message = MavMessage::message_id_from_name(&series.message)
+                .and_then(MavMessage::default_message_from_id)
+                .map_err(|_| invalid("not a known MAVLink message"))?;
+            match fields::field(&message, &series.field) {
+                Some(value) if value.is_number() => {}
+                Some(_) => return Err(invalid("not a numeric field")),
+                None => return Err(invalid("no such field")),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Plot {
+    /// Account for a received frame
+    ///
+    /// Frames of other systems or components than the selected ones only advance the time.
+    pub fn update(&mut self, frame: &Frame) {
+        let header = &frame.header;
+        let selected = self.sysid.is_none_or(|id| id == header.system_id)
+            && self.compid.is_none_or(|id| id == header.component_id);
+        let name = fields::message_name(&frame.message);
+        let mut fields = None;
+        for (series, values) in &mut self.series {
+            if !selected || series.message != name {
+                continue;
+            }
+            let fields = fields.get_or_insert_with(|| fields::fields(&frame.message));
+            if let Some(value) = fields.get(&series.field).and_then(|v| v.as_f64()) {
+                values.push_back((frame.time, value));
+                self.revision += 1;
+            }
+        }
+
+        let now = self.now.map_or(frame.time, |now| now.max(frame.time));
+        self.now = Some(now);
+        for (_, values) in &mut self.series {
+            while let Some((time, _)) = values.front() {
+                match now.duration_since(*time) {
+                    Ok(age) if age > self.window => {
+                        values.pop_front();
+                        self.revision += 1;
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Series {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().split_once('.') {
+            Some((message, field)) if !message.is_empty() && !field.is_empty() => Ok(Series {
+                message: message.to_uppercase(),
+                field: field.to_string(),
+            }),
+            _ => Err(format!("{:?} is not of the form MESSAGE.FIELD", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Series {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.message, self.field)
+    }
+}
+
+// Implementation details
+
+/// How often the charts are redrawn
+const REFRESH: Duration = Duration::from_millis(100);
+
+/// Width of the labels of the y axis
+const LABEL_WIDTH: usize = 10;
+
+const COLORS: [Color; 6] = [
+    Color::GREEN,
+    Color::CYAN,
+    Color::YELLOW,
+    Color::MAGENTA,
+    Color::BLUE,
+    Color::RED,
+];
+
+fn draw(term: &Term<()>, plot: &Plot, playback: Option<&Playback>) -> tuikit::Result<()> {
+    let charts: Vec<_> = plot
+        .series
+        .iter()
+        .zip(COLORS.iter().cycle())
+        .map(|((series, values), color)| Chart {
+            values,
+            now: plot.now,
+            window: plot.window,
+            color: *color,
+            title: match values.back() {
+                Some((_, last)) => format!(" {} = {} ", series, last),
+                None => format!(" {} (waiting for {}) ", series, series.message),
+            },
+        })
+        .collect();
+
+    let controls = playback.map(replay::Controls);
+    let mut root = VSplit::default();
+    for chart in &charts {
+        root = root.split(Win::new(chart).border(true).title(chart.title.as_str()));
+    }
+    if let Some(controls) = &controls {
+        root = root.split(Win::new(controls).basis(1).grow(0).shrink(0));
+    }
+
+    term.clear()?;
+    term.draw(&root)?;
+    term.present()
+}
+
+/// A line chart of one series, drawn with braille characters of 2x4 dots each
+struct Chart<'a> {
+    values: &'a VecDeque<(SystemTime, f64)>,
+    now: Option<SystemTime>,
+    window: Duration,
+    color: Color,
+    title: String,
+}
+
+impl Draw for Chart<'_> {
+    fn draw(&self, canvas: &mut dyn Canvas) -> tuikit::Result<()> {
+        let (width, height) = canvas.size()?;
+        let now = match self.now {
+            Some(now) if width > LABEL_WIDTH + 1 && height > 1 && !self.values.is_empty() => now,
+            _ => return Ok(()),
+        };
+
+        // autoscale to the visible values, with some room for flat lines
+        let (mut min, mut max) = self
+            .values
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(min, max), (_, v)| {
+                (min.min(*v), max.max(*v))
+            });
+        if (max - min).abs() < f64::EPSILON {
+            let margin = (max.abs() * 0.1).max(1.0);
+            min -= margin;
+            max += margin;
+        }
+        canvas.print_with_attr(0, 0, &label(max), Effect::DIM.into())?;
+        canvas.print_with_attr(height - 1, 0, &label(min), Effect::DIM.into())?;
+
+        // the x axis runs from `now - window` on the left to `now` on the right
+        let columns = width - LABEL_WIDTH;
+        let mut dots = Dots::new(columns * 2, height * 4);
+        let window = self.window.as_secs_f64();
+        let points: Vec<_> = self
+            .values
+            .iter()
+            .map(|(time, value)| {
+                let age = now.duration_since(*time).unwrap_or_default().as_secs_f64();
+                let x = (1.0 - age / window) * (dots.width - 1) as f64;
+                let y = (max - value) / (max - min) * (dots.height - 1) as f64;
+                (x, y)
+            })
+            .collect();
+        for pair in points.windows(2) {
+            dots.line(pair[0], pair[1]);
+        }
+        if let [point] = points.as_slice() {
+            dots.line(*point, *point);
+        }
+
+        for row in 0..height {
+            let line = dots.row(row);
+            canvas.print_with_attr(row, LABEL_WIDTH, &line, self.color.into())?;
+        }
+        Ok(())
+    }
+}
+
+impl Widget for Chart<'_> {}
+
+/// A value as label of the y axis
+fn label(value: f64) -> String {
+    let text = match value.abs() {
+        a if a != 0.0 && !(0.01..100_000.0).contains(&a) => format!("{:.2e}", value),
+        _ => format!("{:.2}", value),
+    };
+    format!("{:>w$} ", text, w = LABEL_WIDTH - 1)
+}
+
+/// A grid of dots, drawn as braille characters
+struct Dots {
+    width: usize,
+    height: usize,
+    set: Vec<bool>,
+}
+
+impl Dots {
+    fn new(width: usize, height: usize) -> Self {
+        Dots {
+            width,
+            height,
+            set: vec![false; width * height],
+        }
+    }
+
+    fn set(&mut self, x: f64, y: f64) {
+        let (x, y) = (x.round(), y.round());
+        if x >= 0.0 && y >= 0.0 && (x as usize) < self.width && (y as usize) < self.height {
+            self.set[y as usize * self.width + x as usize] = true;
+        }
+    }
+
+    /// Connect two points with dots
+    fn line(&mut self, (x0, y0): (f64, f64), (x1, y1): (f64, f64)) {
+        let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0) as usize;
+        for step in 0..=steps {
+            let t = step as f64 / steps as f64;
+            self.set(x0 + (x1 - x0) * t, y0 + (y1 - y0) * t);
+        }
+    }
+
+    /// A row of braille characters, covering four rows of dots
+    fn row(&self, row: usize) -> String {
+        // the bits of the dots within a braille character, by column and row
+        const BITS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+        (0..self.width / 2)
+            .map(|column| {
+                let mut bits = 0;
+                for (dx, column_bits) in BITS.iter().enumerate() {
+                    for (dy, bit) in column_bits.iter().enumerate() {
+                        let (x, y) = (column * 2 + dx, row * 4 + dy);
+                        if y < self.height && self.set[y * self.width + x] {
+                            bits |= bit;
+                        }
+                    }
+                }
+                std::char::from_u32(0x2800 + bits).unwrap_or(' ')
+            })
+            .collect()
+    }
+}
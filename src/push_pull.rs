@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, Write};
 use std::path::Path;
@@ -8,7 +9,9 @@ use mavlink::common::*;
 use crate::{
     mavlink_stub::{self, MavlinkConnectionHandler},
     parameters::Parameter,
-    ui,
+    report::Session,
+    rules::{self, Rule},
+    tlog, ui,
     util::*,
 };
 
@@ -35,13 +38,13 @@ pub async fn fetch_parameters(
     let mut param_count = 0;
     for message in smol::stream::block_on(stream) {
         if let MavMessage::PARAM_VALUE(data) = message {
-            param_count = param_count.max(data.param_count as u64) as u64;
+            param_count = param_count.max(data.param_count as u64);
             bar.set_length(param_count);
             bar.set_position(data.param_index as u64 + 1);
             let name = to_string(&data.param_id);
             let value = data.param_value;
 
-            result.push(Parameter { name, value });
+            result.push(Parameter::from_vehicle(name, value, data.param_type));
 
             if bar.position() == param_count {
                 bar.finish();
@@ -50,75 +53,190 @@ pub async fn fetch_parameters(
         }
     }
 
-    result.sort_by(|a, b| a.name.cmp(&b.name));
+    result.sort_by(|a, b| natural_cmp(&a.name, &b.name));
     Ok(result)
 }
 
 /// Read configuration from vehicle and write to file
 pub async fn pull(conn: &MavlinkConnectionHandler, out_file: &Path) -> io::Result<()> {
     let time: DateTime<Local> = Local::now();
-    let parameters = fetch_parameters(&conn).await?;
+    let parameters = fetch_parameters(conn).await?;
 
     let progress = ui::spinner("writing dump");
+    write_file(out_file, &format!("Generated on {:?}", time), &parameters)?;
+    progress.finish();
 
-    let file = File::create(out_file)?;
-    writeln!(
-        &file,
-        "# Generated on {:?} by {}",
-        time,
-        env!("CARGO_PKG_NAME")
-    )?;
-    for Parameter { name, value } in parameters {
-        writeln!(&file, "{},{}", name, value).unwrap();
-    }
+    Ok(())
+}
+
+/// Reconstruct configuration from a telemetry log and write it to file
+///
+/// The last PARAM_VALUE of each parameter in the log is taken. If the log contains the
+/// parameters of several components, e.g. of a gimbal as well, `component` selects one of them
+/// by system and component id.
+pub fn pull_from_log(
+    log: &Path,
+    out_file: &Path,
+    component: (Option<u8>, Option<u8>),
+) -> io::Result<()> {
+    let progress = ui::spinner("extracting parameters");
+    let extracted = extract_parameters(log)?;
     progress.finish();
 
+    let (sysid, compid) = component;
+    let candidates: Vec<_> = extracted
+        .iter()
+        .filter(|((s, c), _)| sysid.is_none_or(|id| id == *s) && compid.is_none_or(|id| id == *c))
+        .collect();
+    let ((sysid, compid), parameters) = match candidates.as_slice() {
+        [candidate] => *candidate,
+        [] => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} contains no matching parameters", log.display()),
+            ))
+        }
+        several => {
+            let components: Vec<_> = several
+                .iter()
+                .map(|((s, c), parameters)| format!("{}:{} ({})", s, c, parameters.len()))
+                .collect();
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} contains parameters of {}, choose one with --sysid and --compid",
+                    log.display(),
+                    components.join(", ")
+                ),
+            ));
+        }
+    };
+
+    let comment = format!(
+        "Extracted from {} (system {}, component {})",
+        log.display(),
+        sysid,
+        compid
+    );
+    write_file(out_file, &comment, parameters)
+}
+
+/// Collect the last value of each parameter in a telemetry log, per system and component
+pub fn extract_parameters(log: &Path) -> io::Result<BTreeMap<(u8, u8), Vec<Parameter>>> {
+    let mut components: BTreeMap<(u8, u8), BTreeMap<String, Parameter>> = BTreeMap::new();
+    for frame in tlog::Reader::open(log)?.filter_map(Result::ok) {
+        if let MavMessage::PARAM_VALUE(data) = frame.message {
+            let name = to_string(&data.param_id);
+            let param = Parameter::from_vehicle(name.clone(), data.param_value, data.param_type);
+            components
+                .entry((frame.header.system_id, frame.header.component_id))
+                .or_default()
+                .insert(name, param);
+        }
+    }
+    Ok(components
+        .into_iter()
+        .map(|(component, parameters)| (component, parameters.into_values().collect()))
+        .collect())
+}
+
+/// Write parameters to a parameter file, starting with a comment on their origin
+pub fn write_file(out_file: &Path, comment: &str, parameters: &[Parameter]) -> io::Result<()> {
+    let file = File::create(out_file)?;
+    writeln!(&file, "# {} by {}", comment, env!("CARGO_PKG_NAME"))?;
+    for Parameter { name, value, .. } in parameters {
+        writeln!(&file, "{},{}", name, value)?;
+    }
     Ok(())
 }
 
 /// Read configuration from file and push to vehicle
-pub async fn push(conn: &MavlinkConnectionHandler, in_file: &Path) -> io::Result<()> {
+///
+/// Violations of the given consistency rules are reported as warnings before pushing. If a
+/// `session` is given, the previous value of each parameter is read, the new value is confirmed
+/// and every change is recorded.
+pub async fn push(
+    conn: &MavlinkConnectionHandler,
+    in_file: &Path,
+    rules: &[Rule],
+    mut session: Option<&mut Session>,
+) -> io::Result<()> {
+    let parameters: Vec<_> = read_file(in_file)?.into_iter().map(|(_, p)| p).collect();
+    for violation in rules::check(rules, &parameters) {
+        ui::warning(&violation.to_string());
+    }
+
     let progress = ui::spinner("applying parameters");
 
+    for param in parameters {
+        progress.set_message(&format!("applying {}", param.name));
+        match session.as_mut() {
+            Some(session) => {
+                let old = Parameter::fetch(conn, &param.name)
+                    .await
+                    .ok()
+                    .map(|p| p.value);
+                if old == Some(param.value) {
+                    continue;
+                }
+                let confirmed = param.push_confirmed(conn).await?;
+                session.record(&param, old, confirmed);
+            }
+            None => param.push(conn).await?,
+        }
+    }
+    progress.finish();
+
+    Ok(())
+}
+
+/// Read all parameters from a parameter file, together with their line numbers
+pub fn read_file(in_file: &Path) -> io::Result<Vec<(usize, Parameter)>> {
     let file = File::open(in_file)?;
     let file = BufReader::new(file);
 
-    for (line_number, line) in file.lines().enumerate() {
-        let line = line?;
-        if line.starts_with('#') {
-            continue;
+    let mut result = Vec::new();
+    for (index, line) in file.lines().enumerate() {
+        let line_number = index + 1;
+        if let Some(param) = parse_line(&line?, line_number)? {
+            result.push((line_number, param));
         }
-        let mut iter = line.split(',');
-        let name = iter
-            .next()
-            .ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("unable to locate parameter name in line {}", line_number),
-                )
-            })?
-            .to_string();
-        let value = iter
-            .next()
-            .ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("unable to locate parameter value in line {}", line_number),
-                )
-            })?
-            .parse()
-            .map_err(|_| {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("unable to parse parameter value in line {}", line_number),
-                )
-            })?;
-
-        progress.set_message(&format!("applying {}", name));
-        let param = Parameter { name, value };
-        param.push(&conn).await?;
     }
-    progress.finish();
+    Ok(result)
+}
 
-    Ok(())
+/// Parse a single line of a parameter file
+///
+/// Returns `None` for comments and empty lines.
+pub fn parse_line(line: &str, line_number: usize) -> io::Result<Option<Parameter>> {
+    if line.starts_with('#') || line.trim().is_empty() {
+        return Ok(None);
+    }
+    let mut iter = line.split(',');
+    let name = iter
+        .next()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unable to locate parameter name in line {}", line_number),
+            )
+        })?
+        .to_string();
+    let value = iter
+        .next()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unable to locate parameter value in line {}", line_number),
+            )
+        })?
+        .parse()
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unable to parse parameter value in line {}", line_number),
+            )
+        })?;
+
+    Ok(Some(Parameter::new(name, value)))
 }
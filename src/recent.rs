@@ -0,0 +1,125 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ui, util, vehicle::Firmware};
+
+/// Search term which limits the results to the recently edited parameters
+pub const FILTER: &str = "@recent";
+
+/// Key to toggle between all and the recently edited parameters
+pub const KEY: &str = "ctrl-r";
+
+/// How many edited parameters are remembered
+const CAPACITY: usize = 32;
+
+/// The last search and edits of `configure`, remembered across runs
+///
+/// There is one file per vehicle, identified by the unique id of its flight controller or at
+/// least its system id, as the parameters edited on one vehicle are of little use when
+/// configuring another one.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Recent {
+    /// The last search query
+    pub query: String,
+    /// The parameter under the cursor when `configure` was left
+    pub selected: Option<String>,
+    /// The recently edited parameters, the most recent one first
+    edited: Vec<String>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+/// Directory the state of past runs is kept in
+///
+/// Can be overridden with the `MAVLINK_CLI_STATE_DIR` environment variable, defaults to
+/// `$XDG_STATE_HOME/mavlink-cli` respectively `~/.local/state/mavlink-cli`.
+pub fn dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("MAVLINK_CLI_STATE_DIR") {
+        return PathBuf::from(dir);
+    }
+    util::xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+impl Recent {
+    /// Load the state of the last run for the given firmware
+    ///
+    /// Starts afresh if there is none yet or it is unreadable. Without a firmware, the state is
+    /// shared by all unidentified vehicles.
+    pub fn load(firmware: Option<&Firmware>) -> Self {
+        let name = match firmware {
+            Some(Firmware {
+                autopilot,
+                vehicle,
+                id: Some(id),
+                ..
+            }) => format!("{}-{}-{}", autopilot, vehicle, id),
+            Some(firmware) => format!("{}-{}", firmware.autopilot, firmware.vehicle),
+            None => String::from("unknown"),
+        };
+        let name: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let path = dir().join("recent").join(format!("{}.json", name));
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                ui::warning(&format!("ignoring {}: {}", path.display(), e));
+                None
+            }
+        };
+        // an unreadable file is overwritten on the next save
+        let mut recent: Recent = content
+            .and_then(|content| match serde_json::from_str(&content) {
+                Ok(recent) => Some(recent),
+                Err(e) => {
+                    ui::warning(&format!("ignoring {}: {}", path.display(), e));
+                    None
+                }
+            })
+            .unwrap_or_default();
+        recent.path = path;
+        recent
+    }
+
+    /// Write the state back to disk for the next run
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// Remember that a parameter was edited
+    pub fn edited(&mut self, name: &str) {
+        self.edited.retain(|n| n != name);
+        self.edited.insert(0, name.to_string());
+        self.edited.truncate(CAPACITY);
+    }
+
+    /// The recently edited parameters, the most recent one first
+    pub fn names(&self) -> &[String] {
+        &self.edited
+    }
+
+    /// Split the `FILTER` off a query, returns whether it was present and the rest of the query
+    pub fn filter(query: &str) -> (bool, String) {
+        let (filters, rest): (Vec<_>, Vec<_>) =
+            query.split_whitespace().partition(|term| *term == FILTER);
+        (!filters.is_empty(), rest.join(" "))
+    }
+
+    /// Add the `FILTER` to a query or remove it from it
+    pub fn toggle(query: &str) -> String {
+        match Self::filter(query) {
+            (true, rest) => rest,
+            (false, rest) if rest.is_empty() => format!("{} ", FILTER),
+            (false, rest) => format!("{} {}", FILTER, rest),
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use std::io::{self, BufRead};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::prelude::*;
+use clap::Clap;
+use mavlink::{common::MavMessage, error::MessageReadError, MavConnection, MavHeader};
+use tuikit::prelude::*;
+
+use futures::prelude::*;
+
+use crate::{
+    listen,
+    mavlink_stub::{Frame, MavlinkConnectionHandler},
+    monitor, plot, stats, statustext, tlog,
+};
+
+// API
+
+/// Which log to replay, how and what to show
+#[derive(Clap, Debug)]
+pub struct Options {
+    /// The telemetry log (tlog) to replay
+    #[clap()]
+    pub file: PathBuf,
+
+    /// Replay this many times faster than recorded, e.g. 10 or 0.5
+    #[clap(long, default_value = "1")]
+    pub speed: f64,
+
+    /// Start paused and replay one message per step, with [n] in the full-screen views or
+    /// [Return] otherwise
+    #[clap(long)]
+    pub step: bool,
+
+    #[clap(subcommand)]
+    pub view: View,
+}
+
+/// What to show of the replayed log, like the subcommands of the same names
+#[derive(Clap, Debug)]
+pub enum View {
+    /// Show the telemetry dashboard
+    Monitor {
+        /// Comma separated list of the panels to show, in this order
+        #[clap(long, use_delimiter = true, default_value = monitor::PANELS)]
+        panels: Vec<monitor::Panel>,
+    },
+    /// Print the messages
+    Listen(listen::Options),
+    /// Show statistics about the messages. Rates are measured by the time of the log, so they
+    /// are the rates at recording time, whatever the speed of the replay.
+    Stats,
+    /// Print the status texts
+    Messages(statustext::Options),
+    /// Plot numeric fields
+    Plot(plot::Options),
+}
+
+/// Controls the pace of a replay
+///
+/// Frames are released at the pace they were recorded, scaled by the speed. Playback may be
+/// paused and stepped through frame by frame.
+#[derive(Debug)]
+pub struct Playback {
+    state: Mutex<State>,
+}
+
+/// Keys controlling the playback in the full-screen views
+pub const KEYS: &str = "[space] pause  [n] step  [+/-] speed";
+
+/// A line showing the state of the playback and the keys to control it
+pub struct Controls<'a>(pub &'a Playback);
+
+/// A MAVLink connection reading from a telemetry log, paced by a `Playback`
+pub struct Connection {
+    source: Mutex<Source>,
+    playback: Arc<Playback>,
+}
+
+/// Show the replayed log in the chosen view until it is closed or, for the printing views, the
+/// log ends
+pub async fn run(
+    conn: Arc<MavlinkConnectionHandler>,
+    playback: &Arc<Playback>,
+    options: &Options,
+) -> io::Result<()> {
+    match &options.view {
+        View::Monitor { panels } => monitor::show(&monitor::watch(conn), panels, Some(playback)),
+        View::Stats => stats::show(&stats::watch(conn), Some(playback)),
+        View::Plot(options) => {
+            let plot = plot::watch(conn, options)?;
+            plot::show(&plot, Some(playback))
+        }
+        View::Listen(listen) => {
+            step_on_return(playback, options.step);
+            until_finished(playback, listen::run(&conn, listen)).await
+        }
+        View::Messages(messages) => {
+            step_on_return(playback, options.step);
+            until_finished(playback, statustext::run(&conn, messages)).await
+        }
+    }
+}
+
+impl Options {
+    /// The playback as requested, fails if the options are invalid
+    pub fn playback(&self) -> io::Result<Playback> {
+        if !(self.speed > 0.0 && self.speed.is_finite()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the speed has to be a positive number",
+            ));
+        }
+        match &self.view {
+            View::Listen(options) => options.validate()?,
+            View::Plot(options) => options.validate()?,
+            _ => {}
+        }
+        Ok(Playback::new(self.speed, self.step))
+    }
+}
+
+impl Playback {
+    pub fn new(speed: f64, paused: bool) -> Self {
+        Playback {
+            state: Mutex::new(State {
+                speed,
+                paused,
+                steps: 0,
+                time: None,
+                raw: None,
+                anchor: None,
+                finished: false,
+            }),
+        }
+    }
+
+    /// The time the last replayed frame was recorded
+    pub fn time(&self) -> Option<SystemTime> {
+        self.state.lock().unwrap().time
+    }
+
+    /// The last replayed frame as it was recorded
+    pub fn raw(&self) -> Option<Arc<[u8]>> {
+        self.state.lock().unwrap().raw.clone()
+    }
+
+    /// Whether the end of the log was reached
+    pub fn finished(&self) -> bool {
+        self.state.lock().unwrap().finished
+    }
+
+    pub fn toggle_pause(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.paused = !state.paused;
+        state.resync();
+    }
+
+    pub fn resume(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.paused = false;
+        state.resync();
+    }
+
+    /// Release one more frame while paused
+    pub fn step(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.paused = true;
+        state.steps += 1;
+    }
+
+    /// Multiply the speed by `factor`
+    pub fn accelerate(&self, factor: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.speed = (state.speed * factor).clamp(MIN_SPEED, MAX_SPEED);
+        state.resync();
+    }
+
+    /// Handle the keys of `KEYS`, returns whether the key was one of them
+    pub fn handle(&self, key: &Key) -> bool {
+        match key {
+            Key::Char(' ') => self.toggle_pause(),
+            Key::Char('n') | Key::Right => self.step(),
+            Key::Char('+') => self.accelerate(2.0),
+            Key::Char('-') => self.accelerate(0.5),
+            _ => return false,
+        }
+        true
+    }
+
+    /// The state of the playback and the time of the log, e.g. `▶ 2x 12:03:44`
+    pub fn status(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let time = match state.time {
+            Some(time) => DateTime::<Local>::from(time)
+                .format("%Y-%m-%d %H:%M:%S%.3f")
+                .to_string(),
+            None => String::from("waiting for the first message"),
+        };
+        match (state.finished, state.paused) {
+            (true, _) => format!("■ end of log {}", time),
+            (false, true) => format!("⏸ paused {}", time),
+            (false, false) => format!("▶ {}x {}", state.speed, time),
+        }
+    }
+}
+
+impl Draw for Controls<'_> {
+    fn draw(&self, canvas: &mut dyn Canvas) -> tuikit::Result<()> {
+        let col = canvas.print_with_attr(0, 0, &self.0.status(), Effect::BOLD.into())?;
+        let keys = format!("  {}  [q] quit", KEYS);
+        canvas.print_with_attr(0, col, &keys, Effect::DIM.into())?;
+        Ok(())
+    }
+}
+
+impl Widget for Controls<'_> {}
+
+impl Connection {
+    pub fn open(path: &std::path::Path, playback: Arc<Playback>) -> io::Result<Self> {
+        Ok(Connection {
+            source: Mutex::new(Source {
+                reader: tlog::Reader::open(path)?,
+                pending: None,
+                started: false,
+            }),
+            playback,
+        })
+    }
+}
+
+impl MavConnection<MavMessage> for Connection {
+    /// Returns the next frame once it is due
+    ///
+    /// Instead of blocking while waiting, this fails with `WouldBlock` every `POLL`, so that the
+    /// caller may handle new subscriptions in between.
+    fn recv(&self) -> std::result::Result<(MavHeader, MavMessage), MessageReadError> {
+        let mut source = self.source.lock().unwrap();
+
+        // give the views the chance to subscribe before the first frame
+        if !source.started {
+            source.started = true;
+            return Err(wait(POLL));
+        }
+        if source.pending.is_none() {
+            match source.reader.next() {
+                Some(Ok(frame)) => source.pending = Some(frame),
+                Some(Err(e)) => return Err(e),
+                None => {
+                    self.playback.state.lock().unwrap().finished = true;
+                    return Err(wait(POLL));
+                }
+            }
+        }
+
+        let time = source.pending.as_ref().map(|frame| frame.time).unwrap();
+        let due = self.playback.state.lock().unwrap().due(time);
+        match due {
+            Some(delay) => Err(wait(delay.min(POLL))),
+            None => {
+                let frame = source.pending.take().unwrap();
+                self.playback.state.lock().unwrap().raw = frame.raw;
+                Ok((frame.header, frame.message))
+            }
+        }
+    }
+
+    /// Nothing is sent to a log
+    fn send(&self, _header: &MavHeader, _data: &MavMessage) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_protocol_version(&mut self, _version: mavlink::MavlinkVersion) {}
+
+    fn get_protocol_version(&self) -> mavlink::MavlinkVersion {
+        mavlink::MavlinkVersion::V1
+    }
+}
+
+// Implementation details
+
+/// The longest `Connection::recv` blocks
+const POLL: Duration = Duration::from_millis(50);
+
+/// Pauses in the log longer than this are skipped, e.g. while the vehicle was disconnected
+const MAX_GAP: Duration = Duration::from_secs(5);
+
+const MIN_SPEED: f64 = 1.0 / 64.0;
+const MAX_SPEED: f64 = 1024.0;
+
+#[derive(Debug)]
+struct State {
+    speed: f64,
+    paused: bool,
+    /// Number of frames to release while paused
+    steps: usize,
+    /// The time the last released frame was recorded
+    time: Option<SystemTime>,
+    /// The last released frame as it was recorded
+    raw: Option<Arc<[u8]>>,
+    /// A moment in real time and the log time corresponding to it
+    anchor: Option<(Instant, SystemTime)>,
+    finished: bool,
+}
+
+impl State {
+    /// How long to wait until the frame recorded at `time` is due, `None` if it is due now
+    fn due(&mut self, time: SystemTime) -> Option<Duration> {
+        if self.paused {
+            return match self.steps {
+                0 => Some(POLL),
+                _ => {
+                    self.steps -= 1;
+                    self.release(time)
+                }
+            };
+        }
+
+        let gap = self.time.and_then(|last| time.duration_since(last).ok());
+        if self.anchor.is_none() || gap.is_some_and(|gap| gap > MAX_GAP) {
+            return self.release(time);
+        }
+        let (start, recorded) = self.anchor?;
+        // frames recorded out of order are released right away
+        let offset = time.duration_since(recorded).unwrap_or_default();
+        let due = start + offset.div_f64(self.speed);
+        match due.checked_duration_since(Instant::now()) {
+            Some(delay) if !delay.is_zero() => Some(delay),
+            _ => {
+                self.time = Some(time);
+                None
+            }
+        }
+    }
+
+    /// Release the frame recorded at `time` now and continue from there
+    fn release(&mut self, time: SystemTime) -> Option<Duration> {
+        self.time = Some(time);
+        self.resync();
+        None
+    }
+
+    /// Continue the playback from the current frame, e.g. after a pause or a change of speed
+    fn resync(&mut self) {
+        self.anchor = self.time.map(|time| (Instant::now(), time));
+    }
+}
+
+struct Source {
+    reader: tlog::Reader,
+    /// The next frame, read but not yet due
+    pending: Option<Frame>,
+    started: bool,
+}
+
+/// Run a printing view until the end of the log
+///
+/// The view is polled first, so that it prints all frames received before the end.
+async fn until_finished(
+    playback: &Playback,
+    view: impl Future<Output = io::Result<()>>,
+) -> io::Result<()> {
+    let finished = async {
+        while !playback.finished() {
+            smol::Timer::after(POLL).await;
+        }
+    };
+    futures::select_biased! {
+        result = view.fuse() => result,
+        _ = finished.fuse() => Ok(()),
+    }
+}
+
+/// Release a frame whenever [Return] is pressed, if stepping, until the input ends
+fn step_on_return(playback: &Arc<Playback>, step: bool) {
+    if !step {
+        return;
+    }
+    eprintln!("press [Return] for the next message");
+    let playback = playback.clone();
+    std::thread::spawn(move || {
+        for _ in io::stdin().lock().lines() {
+            playback.step();
+        }
+        // e.g. piped input, play the rest instead of waiting forever
+        playback.resume();
+    });
+}
+
+/// Sleep for `delay`, then report that no frame is available yet
+fn wait(delay: Duration) -> MessageReadError {
+    std::thread::sleep(delay);
+    MessageReadError::Io(io::ErrorKind::WouldBlock.into())
+}
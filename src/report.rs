@@ -0,0 +1,134 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use chrono::prelude::*;
+use console::style;
+use serde::Serialize;
+
+use crate::parameters::Parameter;
+
+/// A single change of a parameter applied to the vehicle
+#[derive(Debug, Clone, Serialize)]
+pub struct Change {
+    pub name: String,
+    /// Value before the change, if known
+    pub old: Option<f32>,
+    pub new: f32,
+    /// Whether the vehicle reported back the new value
+    pub confirmed: bool,
+    /// Whether the change only takes effect after a reboot of the vehicle
+    pub reboot_required: bool,
+}
+
+/// All changes applied to the vehicle during one session of `configure` or `push`
+#[derive(Debug, Clone, Serialize)]
+pub struct Session {
+    pub started: String,
+    pub changes: Vec<Change>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session {
+            started: Local::now().to_rfc3339(),
+            changes: Vec::new(),
+        }
+    }
+
+    /// Record that `param` was pushed to the vehicle
+    pub fn record(&mut self, param: &Parameter, old: Option<f32>, confirmed: bool) {
+        self.changes.push(Change {
+            name: param.name.clone(),
+            old,
+            new: param.value,
+            confirmed,
+            reboot_required: param.definition().reboot_required,
+        });
+    }
+
+    /// Print a summary table of all changes to stdout
+    pub fn print_summary(&self) {
+        if self.changes.is_empty() {
+            println!("no parameters were changed");
+            return;
+        }
+
+        let width = self
+            .changes
+            .iter()
+            .map(|c| c.name.len())
+            .max()
+            .unwrap_or_default()
+            .max("parameter".len());
+        println!(
+            "{}",
+            style(format!(
+                "{:width$} {:>12} {:>12}  {:9}  reboot",
+                "parameter",
+                "old",
+                "new",
+                "confirmed",
+                width = width
+            ))
+            .bold()
+            .underlined()
+        );
+        for c in &self.changes {
+            println!(
+                "{:width$} {:>12} {:>12}  {:9}  {}",
+                c.name,
+                c.old.map(|v| v.to_string()).unwrap_or_else(|| "?".into()),
+                c.new,
+                match c.confirmed {
+                    true => style("yes").green(),
+                    false => style("no").red().bold(),
+                },
+                match c.reboot_required {
+                    true => style("required").yellow().bold(),
+                    false => style(""),
+                },
+                width = width
+            );
+        }
+    }
+
+    /// Write all changes to a changelog file
+    ///
+    /// The format is JSON if the file name ends with `.json`, Markdown otherwise.
+    pub fn write(&self, out_file: &Path) -> io::Result<()> {
+        let mut file = File::create(out_file)?;
+        if out_file.extension().is_some_and(|e| e == "json") {
+            serde_json::to_writer_pretty(&mut file, self)?;
+            return writeln!(file);
+        }
+
+        writeln!(file, "# Parameter changes\n")?;
+        writeln!(
+            file,
+            "Session started on {} by {}\n",
+            self.started,
+            env!("CARGO_PKG_NAME")
+        )?;
+        writeln!(
+            file,
+            "| Parameter | Old | New | Confirmed | Reboot required |"
+        )?;
+        writeln!(
+            file,
+            "|-----------|----:|----:|-----------|-----------------|"
+        )?;
+        for c in &self.changes {
+            writeln!(
+                file,
+                "| {} | {} | {} | {} | {} |",
+                c.name,
+                c.old.map(|v| v.to_string()).unwrap_or_else(|| "?".into()),
+                c.new,
+                if c.confirmed { "yes" } else { "no" },
+                if c.reboot_required { "yes" } else { "no" }
+            )?;
+        }
+        Ok(())
+    }
+}
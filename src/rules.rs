@@ -0,0 +1,396 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::{
+    definitions::{de_from_str, fill_indices as fill, INDEX_PLACEHOLDER},
+    parameters::Parameter,
+};
+
+// API
+
+/// A consistency rule spanning multiple parameters
+///
+/// Parameter names may contain `INDEX_PLACEHOLDER`, which matches any index including none, so
+/// `BATT{n}_MONITOR` matches `BATT_MONITOR` as well as `BATT2_MONITOR`. User-defined rules are
+/// read from a JSON file containing a list of rules, e.g.
+///
+/// ```json
+/// [
+///     {
+///         "kind": "implies",
+///         "name": "battery-capacity",
+///         "when": "BATT{n}_MONITOR != 0",
+///         "then": "BATT{n}_CAPACITY > 0",
+///         "message": "battery monitor BATT{n} is enabled, but its capacity is zero"
+///     },
+///     {
+///         "kind": "unique",
+///         "name": "serial-protocol",
+///         "param": "SERIAL{n}_PROTOCOL",
+///         "ignore": [-1, 0, 1, 2],
+///         "message": "multiple serial ports use protocol {value}"
+///     }
+/// ]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Rule {
+    /// Whenever `when` holds, `then` has to hold as well
+    ///
+    /// The index matched by `when` is substituted into `then` and `message`.
+    Implies {
+        name: String,
+        #[serde(deserialize_with = "de_from_str")]
+        when: Condition,
+        #[serde(deserialize_with = "de_from_str")]
+        then: Condition,
+        message: String,
+    },
+    /// No two parameters matching `param` may have the same value, unless it is `ignore`d
+    ///
+    /// `{value}` in `message` is replaced by the duplicate value.
+    Unique {
+        name: String,
+        param: String,
+        #[serde(default)]
+        ignore: Vec<f32>,
+        message: String,
+    },
+}
+
+/// A comparison of a parameter against a constant, e.g. `GPS_TYPE != 0`
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub param: String,
+    pub op: Op,
+    pub value: f32,
+}
+
+/// A comparison operator, as used by rules and by the filters of `listen`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A rule which does not hold for a set of parameters
+#[derive(Debug, Clone)]
+pub struct Violation {
+    pub rule: String,
+    pub message: String,
+    /// The parameters involved, the first one is the one triggering the rule
+    pub parameters: Vec<String>,
+}
+
+/// Load the built-in rules and, if given, the user-defined rules from a JSON file
+pub fn load(file: Option<&Path>) -> io::Result<Vec<Rule>> {
+    let mut rules = builtin();
+    if let Some(file) = file {
+        let content = std::fs::read_to_string(file)?;
+        let user: Vec<Rule> = serde_json::from_str(&content).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unable to parse rules from {}: {}", file.display(), e),
+            )
+        })?;
+        rules.extend(user);
+    }
+    Ok(rules)
+}
+
+/// Evaluate all rules against a set of parameters
+///
+/// Rules referring to parameters which are not in the set are skipped.
+pub fn check(rules: &[Rule], parameters: &[Parameter]) -> Vec<Violation> {
+    let values: BTreeMap<&str, f32> = parameters
+        .iter()
+        .map(|p| (p.name.as_str(), p.value))
+        .collect();
+
+    let mut result = Vec::new();
+    for rule in rules {
+        match rule {
+            Rule::Implies {
+                name,
+                when,
+                then,
+                message,
+            } => {
+                for (param, value) in &values {
+                    let indices = match matches(&when.param, param) {
+                        Some(indices) if when.op.apply(*value, when.value) => indices,
+                        _ => continue,
+                    };
+                    let then_param = fill(&then.param, &indices);
+                    match values.get(then_param.as_str()) {
+                        Some(v) if !then.op.apply(*v, then.value) => result.push(Violation {
+                            rule: name.clone(),
+                            message: fill(message, &indices),
+                            parameters: vec![param.to_string(), then_param],
+                        }),
+                        _ => {}
+                    }
+                }
+            }
+            Rule::Unique {
+                name,
+                param,
+                ignore,
+                message,
+            } => {
+                let mut by_value: HashMap<u32, Vec<&str>> = HashMap::new();
+                for (p, value) in &values {
+                    if matches(param, p).is_some() && !ignore.contains(value) {
+                        by_value.entry(value.to_bits()).or_default().push(p);
+                    }
+                }
+                let mut duplicates: Vec<_> = by_value
+                    .into_iter()
+                    .filter(|(_, params)| params.len() > 1)
+                    .collect();
+                duplicates.sort_by(|a, b| a.1.cmp(&b.1));
+                for (value, params) in duplicates {
+                    result.push(Violation {
+                        rule: name.clone(),
+                        message: message.replace("{value}", &f32::from_bits(value).to_string()),
+                        parameters: params.into_iter().map(String::from).collect(),
+                    });
+                }
+            }
+        }
+    }
+    result
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}) [{}]",
+            self.message,
+            self.parameters.join(", "),
+            self.rule
+        )
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("{:?} is not of the form `PARAM OP VALUE`", s);
+        let tokens: Vec<_> = s.split_whitespace().collect();
+        match tokens.as_slice() {
+            [param, op, value] => Ok(Condition {
+                param: param.to_string(),
+                op: op.parse()?,
+                value: value.parse().map_err(|_| err())?,
+            }),
+            _ => Err(err()),
+        }
+    }
+}
+
+impl FromStr for Op {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Op::SYMBOLS
+            .iter()
+            .find(|(symbol, _)| *symbol == s)
+            .map(|(_, op)| *op)
+            .ok_or_else(|| format!("unknown operator {:?}", s))
+    }
+}
+
+impl Op {
+    /// The operators by their symbols, longer symbols first so that `>=` is not taken for `>`
+    pub const SYMBOLS: [(&'static str, Op); 6] = [
+        ("==", Op::Eq),
+        ("!=", Op::Ne),
+        (">=", Op::Ge),
+        ("<=", Op::Le),
+        (">", Op::Gt),
+        ("<", Op::Lt),
+    ];
+
+    pub fn apply<T: PartialOrd>(self, lhs: T, rhs: T) -> bool {
+        match self {
+            Op::Eq => lhs == rhs,
+            Op::Ne => lhs != rhs,
+            Op::Lt => lhs < rhs,
+            Op::Le => lhs <= rhs,
+            Op::Gt => lhs > rhs,
+            Op::Ge => lhs >= rhs,
+        }
+    }
+}
+
+// Implementation details
+
+/// The rules which are always checked
+fn builtin() -> Vec<Rule> {
+    let implies = |name: &str, when: &str, then: &str, message: &str| Rule::Implies {
+        name: name.to_string(),
+        when: when.parse().expect("built-in rules are valid"),
+        then: then.parse().expect("built-in rules are valid"),
+        message: message.to_string(),
+    };
+
+    vec![
+        implies(
+            "gps-type",
+            "SERIAL{n}_PROTOCOL == 5",
+            "GPS_TYPE != 0",
+            "SERIAL{n} is configured for a GPS, but GPS_TYPE is None",
+        ),
+        implies(
+            "battery-capacity",
+            "BATT{n}_MONITOR != 0",
+            "BATT{n}_CAPACITY > 0",
+            "battery monitor BATT{n} is enabled, but its capacity is zero",
+        ),
+        Rule::Unique {
+            name: String::from("serial-protocol"),
+            param: String::from("SERIAL{n}_PROTOCOL"),
+            // None, MAVLink1, MAVLink2 and GPS may be used on multiple ports
+            ignore: vec![-1.0, 0.0, 1.0, 2.0, 5.0],
+            message: String::from("multiple serial ports are configured for protocol {value}"),
+        },
+    ]
+}
+
+/// Match a parameter name against a pattern, returning the indices matched by placeholders
+fn matches<'a>(pattern: &str, name: &'a str) -> Option<Vec<&'a str>> {
+    let mut parts = pattern.split(INDEX_PLACEHOLDER);
+    let mut rest = name.strip_prefix(parts.next().unwrap_or_default())?;
+    let mut indices = Vec::new();
+
+    for part in parts {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        indices.push(&rest[..digits]);
+        rest = rest[digits..].strip_prefix(part)?;
+    }
+
+    match rest.is_empty() {
+        true => Some(indices),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameters(values: &[(&str, f32)]) -> Vec<Parameter> {
+        values
+            .iter()
+            .map(|(name, value)| Parameter::new(name.to_string(), *value))
+            .collect()
+    }
+
+    #[test]
+    fn placeholders_match_any_index_including_none() {
+        assert_eq!(matches("BATT{n}_MONITOR", "BATT_MONITOR"), Some(vec![""]));
+        assert_eq!(matches("BATT{n}_MONITOR", "BATT2_MONITOR"), Some(vec!["2"]));
+        assert_eq!(
+            matches("SR{n}_EXTRA{n}", "SR10_EXTRA3"),
+            Some(vec!["10", "3"])
+        );
+        assert_eq!(matches("GPS_TYPE", "GPS_TYPE"), Some(vec![]));
+        assert_eq!(matches("BATT{n}_MONITOR", "BATT2_MONITOR_X"), None);
+        assert_eq!(matches("BATT{n}_MONITOR", "BATTX_MONITOR"), None);
+        assert_eq!(matches("GPS_TYPE", "GPS_TYPE2"), None);
+    }
+
+    #[test]
+    fn operators_compare_by_their_symbols() {
+        let op = |symbol: &str| symbol.parse::<Op>().unwrap();
+        assert!(op("==").apply(1.0, 1.0));
+        assert!(op("!=").apply(1.0, 2.0));
+        assert!(op(">=").apply(2, 2) && !op(">").apply(2, 2));
+        assert!(op("<=").apply(1, 2) && op("<").apply(1, 2));
+        assert!(!op("<").apply("b", "a"));
+        assert!("=>".parse::<Op>().is_err());
+
+        let condition: Condition = "GPS_TYPE != 0".parse().unwrap();
+        assert_eq!(
+            (condition.param.as_str(), condition.op, condition.value),
+            ("GPS_TYPE", Op::Ne, 0.0)
+        );
+        assert!("GPS_TYPE!=0".parse::<Condition>().is_err());
+        assert!("GPS_TYPE != none".parse::<Condition>().is_err());
+    }
+
+    #[test]
+    fn implications_substitute_the_index_matched() {
+        let params = parameters(&[
+            ("BATT_MONITOR", 4.0),
+            ("BATT_CAPACITY", 0.0),
+            ("BATT2_MONITOR", 4.0),
+            ("BATT2_CAPACITY", 5000.0),
+            ("BATT3_MONITOR", 0.0),
+            ("BATT3_CAPACITY", 0.0),
+            // the implied parameter is missing, the rule is skipped
+            ("BATT4_MONITOR", 4.0),
+        ]);
+        let violations = check(&builtin(), &params);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, "battery-capacity");
+        assert_eq!(
+            violations[0].to_string(),
+            "battery monitor BATT is enabled, but its capacity is zero \
+             (BATT_MONITOR, BATT_CAPACITY) [battery-capacity]"
+        );
+    }
+
+    #[test]
+    fn unique_values_ignore_the_given_ones() {
+        let params = parameters(&[
+            ("SERIAL1_PROTOCOL", 2.0),
+            ("SERIAL2_PROTOCOL", 2.0),
+            ("SERIAL3_PROTOCOL", 23.0),
+            ("SERIAL4_PROTOCOL", 10.0),
+            ("SERIAL5_PROTOCOL", 23.0),
+            ("GPS_TYPE", 1.0),
+        ]);
+        let violations = check(&builtin(), &params);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, "serial-protocol");
+        assert_eq!(
+            violations[0].message,
+            "multiple serial ports are configured for protocol 23"
+        );
+        assert_eq!(
+            violations[0].parameters,
+            ["SERIAL3_PROTOCOL", "SERIAL5_PROTOCOL"]
+        );
+    }
+
+    #[test]
+    fn user_rules_are_read_from_json() {
+        let rules: Vec<Rule> = serde_json::from_str(
+            r#"[{
+                "kind": "implies",
+                "name": "compass",
+                "when": "COMPASS_USE == 1",
+                "then": "COMPASS_ENABLE == 1",
+                "message": "compass used but disabled"
+            }]"#,
+        )
+        .unwrap();
+        let params = parameters(&[("COMPASS_USE", 1.0), ("COMPASS_ENABLE", 0.0)]);
+        let violations = check(&rules, &params);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].parameters, ["COMPASS_USE", "COMPASS_ENABLE"]);
+    }
+}
@@ -1,9 +1,36 @@
 use skim::prelude::*;
+use std::collections::HashSet;
 use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-fn options() -> SkimOptions<'static> {
+use crate::groups::{Entry, Group, Grouped};
+
+/// Key name under which [Escape] or [CTRL+C] is reported, if it is one of the expected keys
+///
+/// This keeps the query the user entered before aborting, instead of an `Interrupted` error.
+pub const ABORT: &str = "esc";
+
+/// The result of a fuzzy finder session
+pub struct Selection<T> {
+    /// The selected items, always empty if the session was ended by one of the expected keys
+    pub items: Vec<T>,
+    /// The key which ended the session, if it was one of the expected keys
+    pub key: Option<String>,
+    /// The query the user entered, to start the next session with
+    pub query: String,
+}
+
+fn options<'a>(
+    header: Option<&'a str>,
+    expect: &'a Option<String>,
+    query: Option<&'a str>,
+) -> SkimOptions<'a> {
     let options = SkimOptionsBuilder::default()
         .height(Some("95%"))
+        .header(header)
+        .query(query)
+        .expect(expect.clone())
         .multi(true)
         //.exact(true)
         .color(Some("16"))
@@ -14,11 +41,14 @@ fn options() -> SkimOptions<'static> {
     options
 }
 
-pub fn select<T>(parameters: &[T]) -> io::Result<Vec<T>>
+/// Let the user select some of the given items with a fuzzy finder
+///
+/// The optional `header` is shown above the list of items. If the user aborts with [Escape] or
+/// [CTRL+C], an `Interrupted` error is returned, unless `ABORT` is one of the expected keys.
+pub fn select<T>(parameters: &[T], header: Option<&str>) -> io::Result<Vec<T>>
 where
     T: Clone + SkimItem,
 {
-    let options = options();
     let (tx_item, rx_item): (SkimItemSender, SkimItemReceiver) = unbounded();
 
     for param in parameters {
@@ -27,10 +57,200 @@ where
 
     drop(tx_item); // so that skim could know when to stop waiting for more items.
 
-    Ok(Skim::run_with(&options, Some(rx_item))
-        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "User hit CTRL+C"))?
-        .selected_items
-        .into_iter()
-        .filter_map(|item| (*item).as_any().downcast_ref::<T>().cloned())
-        .collect())
+    Ok(select_from(rx_item, header, &[], None)?.items)
+}
+
+/// Like `select`, but the items are streamed in through a channel
+///
+/// The user can start searching while items are still arriving. Pressing one of the `keys`, e.g.
+/// `ctrl-z`, ends the session as well and is reported in the returned `Selection`. The session
+/// starts with the given `query`, e.g. the one of the previous session.
+pub fn select_from<T>(
+    rx_item: SkimItemReceiver,
+    header: Option<&str>,
+    keys: &[&str],
+    query: Option<&str>,
+) -> io::Result<Selection<T>>
+where
+    T: Clone + SkimItem,
+{
+    let expect = expect(keys);
+    let options = options(header, &expect, query);
+
+    let output = Skim::run_with(&options, Some(rx_item)).ok_or_else(aborted)?;
+    if output.is_abort {
+        return abort(keys, output.query);
+    }
+
+    if let Some(key) = expected_key(&output.final_key, keys) {
+        return Ok(Selection::key(key, output.query));
+    }
+
+    Ok(Selection {
+        items: output
+            .selected_items
+            .into_iter()
+            .filter_map(|item| (*item).as_any().downcast_ref::<T>().cloned())
+            .collect(),
+        key: None,
+        query: output.query,
+    })
+}
+
+/// Like `select`, but browse the items grouped by their library group and name prefixes
+///
+/// Selecting a single group drills down into it, `..` or [Escape] goes back up. Selecting
+/// multiple entries returns all of the selected items, including all members of selected groups.
+/// Pressing one of the `keys` ends the session on any level of the tree. The `query` is only used
+/// on the top level. The items are taken from `parameters` whenever a group is shown, and again
+/// whenever the `revision` of the items changes while it is shown, so that items which are still
+/// arriving show up. A `revision` of `None` tells that the items no longer change.
+pub fn select_grouped<T>(
+    parameters: impl Fn() -> Vec<T> + Sync,
+    revision: impl Fn() -> Option<usize> + Sync,
+    header: Option<&str>,
+    keys: &[&str],
+    query: Option<&str>,
+) -> io::Result<Selection<T>>
+where
+    T: Clone + SkimItem + Grouped,
+{
+    let expect = expect(keys);
+    let mut options = options(header, &expect, query);
+    // the paths of the groups drilled down into
+    let mut path: Vec<String> = Vec::new();
+
+    loop {
+        let current = |path: &[String]| {
+            let root = Arc::new(Group::tree(&parameters()));
+            match path.last() {
+                Some(last) => root.find(last).unwrap_or(root),
+                None => root,
+            }
+        };
+        let (tx_item, rx_item): (SkimItemSender, SkimItemReceiver) = unbounded();
+        let done = AtomicBool::new(false);
+        let output = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                // owned by the thread, so that skim stops waiting for items once it ends
+                let tx_item = tx_item;
+                let mut sent = HashSet::new();
+                let mut shown = None;
+                while !done.load(Ordering::Relaxed) {
+                    let revision = revision();
+                    if shown != Some(revision) {
+                        for entry in current(&path).entries(path.is_empty()) {
+                            if sent.insert(entry.key()) {
+                                let _ = tx_item.send(Arc::new(entry));
+                            }
+                        }
+                        shown = Some(revision);
+                    }
+                    if revision.is_none() {
+                        break;
+                    }
+                    std::thread::sleep(REFRESH);
+                }
+            });
+            let output = Skim::run_with(&options, Some(rx_item));
+            done.store(true, Ordering::Relaxed);
+            output
+        })
+        .ok_or_else(aborted)?;
+        options.query = None;
+
+        if let Some(key) = expected_key(&output.final_key, keys) {
+            return Ok(Selection::key(key, output.query));
+        }
+
+        let selected: Vec<_> = output
+            .selected_items
+            .iter()
+            .filter_map(|item| (**item).as_any().downcast_ref::<Entry<T>>())
+            .collect();
+
+        match selected.as_slice() {
+            [] if output.is_abort && !path.is_empty() => {
+                path.pop();
+            }
+            [] if output.is_abort => return abort(keys, output.query),
+            [] => return Ok(Selection::from(Vec::new()).with_query(output.query)),
+            [Entry::Up] => {
+                path.pop();
+            }
+            [Entry::Group(group)] => path.push(group.path.clone()),
+            entries => {
+                return Ok(Selection::from(
+                    entries
+                        .iter()
+                        .flat_map(|entry| match entry {
+                            Entry::Up => Vec::new(),
+                            Entry::Group(group) => group.all_items(),
+                            Entry::Item(item) => vec![item.clone()],
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .with_query(output.query))
+            }
+        }
+    }
+}
+
+impl<T> Selection<T> {
+    fn key(key: String, query: String) -> Self {
+        Selection {
+            items: Vec::new(),
+            key: Some(key),
+            query,
+        }
+    }
+
+    fn with_query(self, query: String) -> Self {
+        Selection { query, ..self }
+    }
+}
+
+impl<T> From<Vec<T>> for Selection<T> {
+    fn from(items: Vec<T>) -> Self {
+        Selection {
+            items,
+            key: None,
+            query: String::new(),
+        }
+    }
+}
+
+// Implementation details
+
+/// How often `select_grouped` checks the revision of the items
+const REFRESH: Duration = Duration::from_millis(250);
+
+/// Skim expects the keys as a comma separated list
+///
+/// `ABORT` is left out, as skim aborts on it anyway.
+fn expect(keys: &[&str]) -> Option<String> {
+    let keys: Vec<_> = keys.iter().filter(|key| **key != ABORT).copied().collect();
+    Some(keys.join(",")).filter(|keys| !keys.is_empty())
+}
+
+fn expected_key(final_key: &Key, keys: &[&str]) -> Option<String> {
+    let name = match final_key {
+        Key::Ctrl(c) => format!("ctrl-{}", c),
+        Key::Alt(c) => format!("alt-{}", c),
+        Key::Char(c) => c.to_string(),
+        _ => return None,
+    };
+    Some(name).filter(|name| keys.contains(&name.as_str()))
+}
+
+/// The result of an aborted session, see `ABORT`
+fn abort<T>(keys: &[&str], query: String) -> io::Result<Selection<T>> {
+    match keys.contains(&ABORT) {
+        true => Ok(Selection::key(ABORT.to_string(), query)),
+        false => Err(aborted()),
+    }
+}
+
+fn aborted() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "aborted by the user")
 }
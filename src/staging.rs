@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use console::style;
+use dialoguer::Select;
+
+use crate::parameters::Parameter;
+
+/// Edits of parameters which were not yet pushed to the vehicle
+///
+/// This allows to change multiple parameters which depend on each other, e.g. the protocol and
+/// baud rate of a serial port, and apply them together after reviewing them.
+#[derive(Debug, Default)]
+pub struct Staging {
+    /// The edited parameters together with the value they had on the vehicle before
+    pending: BTreeMap<String, (f32, Parameter)>,
+}
+
+/// What to do with the staged edits after reviewing them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Commit,
+    Discard,
+    Continue,
+}
+
+impl Staging {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stage an edit of a parameter, `old` being the value currently set on the vehicle
+    ///
+    /// Editing a parameter back to its value on the vehicle removes it from the staging area.
+    pub fn stage(&mut self, param: Parameter, old: f32) {
+        let old = self.pending.get(&param.name).map_or(old, |(old, _)| *old);
+        if param.value == old {
+            self.pending.remove(&param.name);
+        } else {
+            self.pending.insert(param.name.clone(), (old, param));
+        }
+    }
+
+    /// The staged version of a parameter, if it was edited
+    pub fn get(&self, name: &str) -> Option<&Parameter> {
+        self.pending.get(name).map(|(_, param)| param)
+    }
+
+    /// Replace the parameters with their staged versions
+    pub fn overlay(&self, parameters: Vec<Parameter>) -> Vec<Parameter> {
+        parameters
+            .into_iter()
+            .map(|param| self.get(&param.name).cloned().unwrap_or(param))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Remove all staged edits, returning them with their previous values
+    pub fn take(&mut self) -> Vec<(f32, Parameter)> {
+        std::mem::take(&mut self.pending).into_values().collect()
+    }
+
+    /// The staged edits with their previous values, leaving them staged
+    ///
+    /// The edits are committed one at a time with [`Staging::unstage`], so that the ones which
+    /// fail stay staged.
+    pub fn pending(&self) -> Vec<(f32, Parameter)> {
+        self.pending.values().cloned().collect()
+    }
+
+    /// Remove a staged edit after it was pushed to the vehicle
+    pub fn unstage(&mut self, name: &str) {
+        self.pending.remove(name);
+    }
+
+    /// Show the pending diff and ask the user what to do with it
+    pub fn review(&self) -> io::Result<Decision> {
+        println!(
+            "\n{}\n",
+            style(format!("{} staged changes", self.len()))
+                .bold()
+                .underlined()
+        );
+        for (old, param) in self.pending.values() {
+            let def = param.definition();
+            println!(
+                "{} {}",
+                style(&param.name).bold(),
+                style(&def.display_name).dim()
+            );
+            println!(
+                "  {} {}",
+                style("-").red(),
+                def.describe_value(*old, Some(param.param_type))
+            );
+            println!(
+                "  {} {}",
+                style("+").green(),
+                def.describe_value(param.value, Some(param.param_type))
+            );
+            if def.reboot_required {
+                println!("  {}", style("reboot required").yellow().bold());
+            }
+        }
+        println!();
+
+        let choices = [
+            format!("commit {} changes to the vehicle", self.len()),
+            String::from("discard all changes"),
+            String::from("continue editing"),
+        ];
+        let choice = Select::new().items(&choices).default(0).interact()?;
+        Ok(match choice {
+            0 => Decision::Commit,
+            1 => Decision::Discard,
+            _ => Decision::Continue,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str, value: f32) -> Parameter {
+        Parameter::new(name.to_string(), value)
+    }
+
+    fn values(staging: &Staging) -> Vec<(f32, String, f32)> {
+        staging
+            .pending()
+            .into_iter()
+            .map(|(old, param)| (old, param.name, param.value))
+            .collect()
+    }
+
+    #[test]
+    fn edits_keep_the_value_on_the_vehicle() {
+        let mut staging = Staging::new();
+        staging.stage(param("SERIAL1_BAUD", 57.0), 115.0);
+        staging.stage(param("SERIAL1_PROTOCOL", 2.0), 1.0);
+        // editing again keeps the value the vehicle had before the first edit
+        staging.stage(param("SERIAL1_BAUD", 921.0), 57.0);
+        assert_eq!(
+            values(&staging),
+            [
+                (115.0, String::from("SERIAL1_BAUD"), 921.0),
+                (1.0, String::from("SERIAL1_PROTOCOL"), 2.0)
+            ]
+        );
+        assert_eq!(staging.get("SERIAL1_BAUD").unwrap().value, 921.0);
+
+        let overlay = staging.overlay(vec![param("SERIAL1_BAUD", 115.0), param("GPS_TYPE", 1.0)]);
+        let overlay: Vec<_> = overlay.iter().map(|p| p.value).collect();
+        assert_eq!(overlay, [921.0, 1.0]);
+
+        // editing back to the value on the vehicle drops the edit
+        staging.stage(param("SERIAL1_BAUD", 115.0), 921.0);
+        assert_eq!(staging.len(), 1);
+        assert!(staging.get("SERIAL1_BAUD").is_none());
+    }
+
+    #[test]
+    fn edits_stay_staged_until_committed() {
+        let mut staging = Staging::new();
+        staging.stage(param("A", 1.0), 0.0);
+        staging.stage(param("B", 2.0), 0.0);
+
+        // pending leaves the edits staged, only the committed one is removed
+        assert_eq!(staging.pending().len(), 2);
+        staging.unstage("A");
+        assert_eq!(values(&staging), [(0.0, String::from("B"), 2.0)]);
+
+        assert_eq!(staging.take().len(), 1);
+        assert!(staging.is_empty());
+    }
+}
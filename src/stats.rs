@@ -0,0 +1,397 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use futures::prelude::*;
+use mavlink::Message;
+use tuikit::prelude::*;
+
+use crate::{
+    fields,
+    mavlink_stub::{Frame, MavlinkConnectionHandler},
+    replay::{self, Playback},
+    tui::to_io,
+    util,
+};
+
+// API
+
+/// Statistics about the received messages, per system and component
+///
+/// Rates are measured by the time the frames were received, which is the time they were recorded
+/// when replaying a log.
+#[derive(Debug, Default)]
+pub struct Stats {
+    links: BTreeMap<(u8, u8), Link>,
+    /// Number of frames which could not be decoded, see `MavlinkConnectionHandler::parse_errors`
+    pub parse_errors: usize,
+}
+
+/// Show a live table of message rates and link quality until [q] or [Escape] is pressed
+pub fn run(conn: Arc<MavlinkConnectionHandler>) -> io::Result<()> {
+    let stats = watch(conn);
+    show(&stats, None)
+}
+
+/// Keep the statistics up to date in the background
+pub fn watch(conn: Arc<MavlinkConnectionHandler>) -> Arc<Mutex<Stats>> {
+    let stats = Arc::new(Mutex::new(Stats::default()));
+    util::spawn({
+        let stats = stats.clone();
+        move || async move {
+            let mut frames = conn.tap().await;
+            while let Some(frame) = frames.next().await {
+                let mut stats = stats.lock().unwrap();
+                stats.update(&frame);
+                stats.parse_errors = conn.parse_errors();
+            }
+        }
+    });
+    stats
+}
+
+/// Draw the statistics until [q] or [Escape] is pressed
+///
+/// When replaying a log, its `playback` is shown and controlled as well.
+pub fn show(stats: &Mutex<Stats>, playback: Option<&Playback>) -> io::Result<()> {
+    let term: Term<()> = Term::new().map_err(to_io)?;
+    let mut scroll = 0;
+    // the table is only drawn again once its contents or the playback changed
+    let mut drawn = None;
+    loop {
+        // a replay measures by the time of the log, so that the rates do not depend on its speed
+        let now = playback
+            .and_then(Playback::time)
+            .unwrap_or_else(SystemTime::now);
+        let rows = stats.lock().unwrap().rows(now);
+        let state = (rows, scroll, playback.map(Playback::status));
+        if drawn.as_ref() != Some(&state) {
+            let table = Table {
+                rows: &state.0,
+                scroll,
+                playback,
+            };
+            term.clear().map_err(to_io)?;
+            term.draw(&table).map_err(to_io)?;
+            term.present().map_err(to_io)?;
+            drawn = Some(state);
+        }
+        let rows = drawn.as_ref().map_or(0, |(rows, ..)| rows.len());
+
+        match term.peek_event(REFRESH) {
+            Ok(Event::Key(Key::Char('q'))) | Ok(Event::Key(Key::ESC)) => break,
+            Ok(Event::Key(Key::Ctrl('c'))) => break,
+            Ok(Event::Key(Key::Up)) | Ok(Event::Key(Key::Char('k'))) => {
+                scroll = scroll.saturating_sub(1)
+            }
+            Ok(Event::Key(Key::Down)) | Ok(Event::Key(Key::Char('j'))) => {
+                scroll = (scroll + 1).min(rows.saturating_sub(1))
+            }
+            Ok(Event::Key(key)) => {
+                if let Some(playback) = playback {
+                    playback.handle(&key);
+                }
+            }
+            Ok(Event::Resize { .. }) => drawn = None,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+impl Stats {
+    /// Account for a received frame
+    pub fn update(&mut self, frame: &Frame) {
+        let now = frame.time;
+        let header = &frame.header;
+        let link = self
+            .links
+            .entry((header.system_id, header.component_id))
+            .or_default();
+
+        if let Some(last) = link.sequence {
+            // the sequence number wraps around after 255, large gaps are rather duplicated or
+            // reordered messages
+            let gap = header.sequence.wrapping_sub(last).wrapping_sub(1);
+            if gap < 128 {
+                link.lost += u64::from(gap);
+            }
+        }
+        link.sequence = Some(header.sequence);
+
+        let bytes = frame_len(&frame.message);
+        link.total.add(now, bytes);
+        link.messages
+            .entry(fields::message_name(&frame.message))
+            .or_default()
+            .add(now, bytes);
+    }
+
+    /// The table to show, a summary row per link followed by a row per message type
+    fn rows(&self, now: SystemTime) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for ((sysid, compid), link) in &self.links {
+            let received = link.total.count;
+            let loss = match received + link.lost {
+                0 => 0.0,
+                sent => link.lost as f64 * 100.0 / sent as f64,
+            };
+            rows.push(Row::Link {
+                name: format!("{}:{}", sysid, compid),
+                received,
+                lost: link.lost,
+                loss,
+                rate: link.total.rate(now),
+                bytes: link.total.bytes_per_second(now),
+            });
+            for (name, counter) in &link.messages {
+                rows.push(Row::Message {
+                    name: name.clone(),
+                    count: counter.count,
+                    rate: counter.rate(now),
+                    bytes: counter.bytes_per_second(now),
+                });
+            }
+        }
+        rows.push(Row::Errors(self.parse_errors));
+        rows
+    }
+}
+
+// Implementation details
+
+/// How often the table is redrawn
+const REFRESH: Duration = Duration::from_millis(500);
+
+/// Rates are averaged over this period
+const WINDOW: Duration = Duration::from_secs(5);
+
+/// Overhead of a MAVLink 1 frame: start marker, length, sequence, system, component, message id
+/// and checksum
+const FRAME_OVERHEAD: usize = 8;
+
+/// The messages received from one component of a system
+#[derive(Debug, Default)]
+struct Link {
+    /// The last sequence number received
+    sequence: Option<u8>,
+    /// Number of messages missing in the sequence
+    lost: u64,
+    total: Counter,
+    messages: BTreeMap<String, Counter>,
+}
+
+/// Counts messages and bytes, remembering the recent arrivals to calculate rates
+#[derive(Debug, Default)]
+struct Counter {
+    count: u64,
+    first: Option<SystemTime>,
+    recent: VecDeque<(SystemTime, usize)>,
+}
+
+impl Counter {
+    fn add(&mut self, now: SystemTime, bytes: usize) {
+        self.count += 1;
+        if self.recent.back().is_some_and(|(time, _)| *time > now) {
+            // the clock or the log jumped back, the earlier arrivals no longer make sense
+            self.recent.clear();
+            self.first = None;
+        }
+        self.first.get_or_insert(now);
+        self.recent.push_back((now, bytes));
+        while let Some((time, _)) = self.recent.front() {
+            match elapsed(*time, now) > WINDOW {
+                true => drop(self.recent.pop_front()),
+                false => break,
+            }
+        }
+    }
+
+    /// The arrivals within the window before `now`
+    fn window(&self, now: SystemTime) -> impl Iterator<Item = &(SystemTime, usize)> {
+        self.recent
+            .iter()
+            .filter(move |(time, _)| elapsed(*time, now) <= WINDOW)
+    }
+
+    /// The length of the window, shorter while the first messages arrive
+    fn period(&self, now: SystemTime) -> f64 {
+        let elapsed = self.first.map_or(WINDOW, |first| elapsed(first, now));
+        elapsed.clamp(Duration::from_secs(1), WINDOW).as_secs_f64()
+    }
+
+    /// Messages per second
+    fn rate(&self, now: SystemTime) -> f64 {
+        self.window(now).count() as f64 / self.period(now)
+    }
+
+    fn bytes_per_second(&self, now: SystemTime) -> f64 {
+        self.window(now).map(|(_, bytes)| *bytes).sum::<usize>() as f64 / self.period(now)
+    }
+}
+
+/// The time from `earlier` to `now`, zero if `earlier` is later
+fn elapsed(earlier: SystemTime, now: SystemTime) -> Duration {
+    now.duration_since(earlier).unwrap_or_default()
+}
+
+/// The size of a message on the wire
+fn frame_len(message: &mavlink::common::MavMessage) -> usize {
+    message.ser().len() + FRAME_OVERHEAD
+}
+
+#[derive(PartialEq)]
+enum Row {
+    Link {
+        name: String,
+        received: u64,
+        lost: u64,
+        loss: f64,
+        rate: f64,
+        bytes: f64,
+    },
+    Message {
+        name: String,
+        count: u64,
+        rate: f64,
+        bytes: f64,
+    },
+    Errors(usize),
+}
+
+struct Table<'a> {
+    rows: &'a [Row],
+    scroll: usize,
+    playback: Option<&'a Playback>,
+}
+
+impl Draw for Table<'_> {
+    fn draw(&self, canvas: &mut dyn Canvas) -> tuikit::Result<()> {
+        let (_, height) = canvas.size()?;
+        let header = format!(
+            "{:<28} {:>10} {:>10} {:>10} {:>12}",
+            "system:component / message", "count", "rate Hz", "bytes/s", "lost"
+        );
+        canvas.print_with_attr(0, 0, &header, Effect::BOLD.into())?;
+
+        let bold = Attr {
+            fg: Color::CYAN,
+            effect: Effect::BOLD,
+            ..Attr::default()
+        };
+        for (row, line) in self
+            .rows
+            .iter()
+            .skip(self.scroll)
+            .take(height.saturating_sub(2))
+            .enumerate()
+        {
+            let (text, attr) = match line {
+                Row::Link {
+                    name,
+                    received,
+                    lost,
+                    loss,
+                    rate,
+                    bytes,
+                } => (
+                    format!(
+                        "{:<28} {:>10} {:>10.1} {:>10.0} {:>5} {:>5.1}%",
+                        name, received, rate, bytes, lost, loss
+                    ),
+                    bold,
+                ),
+                Row::Message {
+                    name,
+                    count,
+                    rate,
+                    bytes,
+                } => (
+                    format!(
+                        "  {:<26} {:>10} {:>10.1} {:>10.0}",
+                        name, count, rate, bytes
+                    ),
+                    Attr::default(),
+                ),
+                Row::Errors(errors) => (
+                    format!("{:<28} {:>10}", "undecodable frames", errors),
+                    match errors {
+                        0 => Attr::from(Effect::DIM),
+                        _ => Attr::from(Color::RED),
+                    },
+                ),
+            };
+            canvas.print_with_attr(row + 1, 0, &text, attr)?;
+        }
+        let footer = height.saturating_sub(1);
+        match self.playback {
+            Some(playback) => {
+                let col =
+                    canvas.print_with_attr(footer, 0, &playback.status(), Effect::BOLD.into())?;
+                let keys = format!("  {}  [↑↓] scroll  [q] quit", replay::KEYS);
+                canvas.print_with_attr(footer, col, &keys, Effect::DIM.into())?;
+            }
+            None => {
+                canvas.print_with_attr(footer, 0, "[↑↓] scroll  [q] quit", Effect::DIM.into())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Widget for Table<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::UNIX_EPOCH;
+
+    fn at(millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn rates_are_averaged_over_the_window() {
+        let mut counter = Counter::default();
+        assert_eq!(counter.rate(at(0)), 0.0);
+
+        // 10 Hz of 20 bytes each, over less than a second at first
+        for i in 0..5 {
+            counter.add(at(i * 100), 20);
+        }
+        assert_eq!(counter.rate(at(400)), 5.0);
+        assert_eq!(counter.bytes_per_second(at(400)), 100.0);
+
+        // then over the time since the first message
+        for i in 5..20 {
+            counter.add(at(i * 100), 20);
+        }
+        assert_eq!(counter.rate(at(2000)), 10.0);
+
+        // and over the window at most, the earlier messages leave it
+        for i in 20..100 {
+            counter.add(at(i * 100), 20);
+        }
+        assert_eq!(counter.count, 100);
+        assert_eq!(counter.rate(at(9900)), 51.0 / 5.0);
+        assert_eq!(counter.rate(at(9950)), 10.0);
+        assert_eq!(counter.bytes_per_second(at(9950)), 200.0);
+
+        // no more messages
+        assert_eq!(counter.rate(at(20_000)), 0.0);
+    }
+
+    #[test]
+    fn going_back_in_time_starts_over() {
+        let mut counter = Counter::default();
+        for i in 0..50 {
+            counter.add(at(10_000 + i * 100), 1);
+        }
+        counter.add(at(0), 1);
+        assert_eq!(counter.count, 51);
+        assert_eq!(counter.recent.len(), 1);
+        assert_eq!(counter.rate(at(0)), 1.0);
+    }
+}
@@ -0,0 +1,436 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use chrono::prelude::*;
+use clap::Clap;
+use console::{style, StyledObject};
+use futures::prelude::*;
+use mavlink::common::{MavMessage, MavSeverity, STATUSTEXT_DATA};
+
+use crate::{
+    mavlink_stub::{Frame, MavlinkConnectionHandler},
+    tlog, util,
+};
+
+// API
+
+/// Which status texts to show and where to log them
+#[derive(Clap, Debug, Clone)]
+pub struct Options {
+    /// Only show texts of at least this severity: emergency, alert, critical, error, warning,
+    /// notice, info or debug
+    #[clap(short, long, default_value = "debug")]
+    pub severity: Severity,
+
+    /// Append the texts to this file as well
+    #[clap(long)]
+    pub log: Option<PathBuf>,
+}
+
+/// The lowest severity of interest
+#[derive(Debug, Clone, Copy)]
+pub struct Severity(pub MavSeverity);
+
+/// A text sent by the vehicle, e.g. a warning or a pre-arm failure
+#[derive(Debug, Clone)]
+pub struct StatusText {
+    pub time: SystemTime,
+    pub sysid: u8,
+    pub compid: u8,
+    pub severity: MavSeverity,
+    pub text: String,
+}
+
+/// The most recent status texts
+#[derive(Debug, Default)]
+pub struct Console {
+    pub texts: VecDeque<StatusText>,
+    /// Number of texts received so far, to notice new ones
+    pub received: usize,
+}
+
+/// Joins status texts which were split into chunks
+///
+/// A STATUSTEXT carries at most 50 characters. Longer texts are split into chunks which share the
+/// `id` of MAVLink 2 and are numbered by its `chunk_seq`. These extensions are unknown to the
+/// message definitions used here, so they are read from the raw frame where it is known, i.e.
+/// when replaying logs. A text filling all 50 characters is continued by the next chunk with the
+/// same `id`, while `id` 0 marks a text which is not split. For MAVLink 1 frames and links which
+/// do not hand out raw frames, a text filling all 50 characters is taken to be continued by the
+/// next text of the same component instead. Either way, chunks which are not continued in time
+/// complete the text.
+#[derive(Debug, Default)]
+pub struct Assembler {
+    /// The incomplete texts by system, component and `id`, which is 0 if unknown
+    pending: HashMap<(u8, u8, u16), StatusText>,
+}
+
+/// How texts of a severity stand out, for both the console and the full-screen views
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Look {
+    pub colour: Option<Colour>,
+    pub emphasis: Emphasis,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colour {
+    Red,
+    Yellow,
+    Cyan,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emphasis {
+    Bold,
+    Normal,
+    Dim,
+}
+
+/// Print the status texts of the vehicle until interrupted, and log them if requested
+pub async fn run(conn: &MavlinkConnectionHandler, options: &Options) -> io::Result<()> {
+    let mut log = match &options.log {
+        Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+        None => None,
+    };
+    let mut assembler = Assembler::default();
+    let mut frames = conn.tap().await;
+    while let Some(frame) = frames.next().await {
+        for text in assembler.update(&frame) {
+            if !options.severity.includes(text.severity) {
+                continue;
+            }
+            if let Some(log) = &mut log {
+                write_log(log, &text)?;
+            }
+            println!("{}", text.styled());
+        }
+    }
+    Ok(())
+}
+
+/// Collect the status texts of the vehicle in the background
+pub fn watch(conn: Arc<MavlinkConnectionHandler>) -> Arc<Mutex<Console>> {
+    let console = Arc::new(Mutex::new(Console::default()));
+    util::spawn({
+        let console = console.clone();
+        move || async move {
+            let mut assembler = Assembler::default();
+            let mut frames = conn.tap().await;
+            while let Some(frame) = frames.next().await {
+                let texts = assembler.update(&frame);
+                let mut console = console.lock().unwrap();
+                for text in texts {
+                    console.push(text);
+                }
+            }
+        }
+    });
+    console
+}
+
+impl Console {
+    fn push(&mut self, text: StatusText) {
+        if self.texts.len() == CAPACITY {
+            self.texts.pop_front();
+        }
+        self.texts.push_back(text);
+        self.received += 1;
+    }
+}
+
+impl Assembler {
+    /// Account for a received frame, returns the texts which are complete now
+    ///
+    /// Frames of other messages are needed as well, to notice chunks which were never continued.
+    pub fn update(&mut self, frame: &Frame) -> Vec<StatusText> {
+        let mut complete = Vec::new();
+        let stale: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, text)| {
+                frame
+                    .time
+                    .duration_since(text.time)
+                    .is_ok_and(|age| age > CHUNK_TIMEOUT)
+            })
+            .map(|(key, _)| *key)
+            .collect();
+        for key in stale {
+            complete.extend(self.pending.remove(&key));
+        }
+
+        let data = match &frame.message {
+            MavMessage::STATUSTEXT(data) => data,
+            _ => return complete,
+        };
+        let numbering = frame.raw.as_deref().and_then(chunk_numbering);
+        let id = numbering.map_or(0, |(id, _)| id);
+        let key = (frame.header.system_id, frame.header.component_id, id);
+        let first = numbering.is_some_and(|(_, seq)| seq == 0);
+        let chunk = util::to_string(&data.text);
+        let text = match self.pending.remove(&key) {
+            Some(mut text) if !first && text.severity == data.severity => {
+                text.text.push_str(&chunk);
+                text
+            }
+            pending => {
+                complete.extend(pending);
+                StatusText {
+                    time: frame.time,
+                    sysid: key.0,
+                    compid: key.1,
+                    severity: data.severity,
+                    text: chunk,
+                }
+            }
+        };
+        let continued = match numbering {
+            Some((0, _)) => false,
+            _ => is_continued(data),
+        };
+        match continued {
+            true => drop(self.pending.insert(key, text)),
+            false => complete.push(text),
+        }
+        complete
+    }
+}
+
+impl StatusText {
+    /// The text with its time, origin and severity, e.g. for a log file
+    pub fn format(&self) -> String {
+        let time: DateTime<Local> = self.time.into();
+        format!(
+            "{} {:>3}:{:<3} {:<9} {}",
+            time.format("%Y-%m-%d %H:%M:%S%.3f"),
+            self.sysid,
+            self.compid,
+            severity_name(self.severity),
+            self.text
+        )
+    }
+
+    /// The text with its time and severity, coloured by severity
+    pub fn styled(&self) -> String {
+        let time: DateTime<Local> = self.time.into();
+        format!(
+            "{} {} {}",
+            style(time.format("%H:%M:%S")).dim(),
+            colour(
+                self.severity,
+                style(format!("{:<9}", severity_name(self.severity)))
+            ),
+            colour(self.severity, style(&self.text))
+        )
+    }
+}
+
+impl Severity {
+    /// Whether texts of `severity` are of interest, lower values are more severe
+    pub fn includes(&self, severity: MavSeverity) -> bool {
+        severity as u8 <= self.0 as u8
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SEVERITIES
+            .iter()
+            .find(|severity| severity_name(**severity).eq_ignore_ascii_case(s.trim()))
+            .map(|severity| Severity(*severity))
+            .ok_or_else(|| format!("{:?} is not a known severity", s))
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(severity_name(self.0))
+    }
+}
+
+/// How texts of a severity stand out, the more severe the more
+pub fn look(severity: MavSeverity) -> Look {
+    let (colour, emphasis) = match severity as u8 {
+        0..=2 => (Some(Colour::Red), Emphasis::Bold),
+        3 => (Some(Colour::Red), Emphasis::Normal),
+        4 => (Some(Colour::Yellow), Emphasis::Normal),
+        5 => (Some(Colour::Cyan), Emphasis::Normal),
+        6 => (None, Emphasis::Normal),
+        _ => (None, Emphasis::Dim),
+    };
+    Look { colour, emphasis }
+}
+
+/// The name of a severity without its prefix, e.g. `WARNING`
+pub fn severity_name(severity: MavSeverity) -> &'static str {
+    match severity {
+        MavSeverity::MAV_SEVERITY_EMERGENCY => "EMERGENCY",
+        MavSeverity::MAV_SEVERITY_ALERT => "ALERT",
+        MavSeverity::MAV_SEVERITY_CRITICAL => "CRITICAL",
+        MavSeverity::MAV_SEVERITY_ERROR => "ERROR",
+        MavSeverity::MAV_SEVERITY_WARNING => "WARNING",
+        MavSeverity::MAV_SEVERITY_NOTICE => "NOTICE",
+        MavSeverity::MAV_SEVERITY_INFO => "INFO",
+        MavSeverity::MAV_SEVERITY_DEBUG => "DEBUG",
+    }
+}
+
+// Implementation details
+
+/// Number of texts kept by `Console`
+const CAPACITY: usize = 100;
+
+/// The length of the text field of STATUSTEXT
+const CHUNK_LEN: usize = 50;
+
+/// How long to wait for the continuation of a chunked text
+const CHUNK_TIMEOUT: Duration = Duration::from_millis(500);
+
+const SEVERITIES: [MavSeverity; 8] = [
+    MavSeverity::MAV_SEVERITY_EMERGENCY,
+    MavSeverity::MAV_SEVERITY_ALERT,
+    MavSeverity::MAV_SEVERITY_CRITICAL,
+    MavSeverity::MAV_SEVERITY_ERROR,
+    MavSeverity::MAV_SEVERITY_WARNING,
+    MavSeverity::MAV_SEVERITY_NOTICE,
+    MavSeverity::MAV_SEVERITY_INFO,
+    MavSeverity::MAV_SEVERITY_DEBUG,
+];
+
+/// Whether a text uses up all of its characters, so that another chunk may follow
+fn is_continued(data: &STATUSTEXT_DATA) -> bool {
+    data.text.len() >= CHUNK_LEN && !data.text.contains(&'\0')
+}
+
+/// The length of the payload of STATUSTEXT including the extensions
+const PAYLOAD_LEN: usize = 54;
+
+/// The `id` and `chunk_seq` of a STATUSTEXT, if it is a raw MAVLink 2 frame
+fn chunk_numbering(raw: &[u8]) -> Option<(u16, u8)> {
+    let payload = tlog::v2_payload(raw, PAYLOAD_LEN)?;
+    // severity and text precede the extensions
+    let id = u16::from_le_bytes([payload[51], payload[52]]);
+    Some((id, payload[53]))
+}
+
+fn colour<D>(severity: MavSeverity, text: StyledObject<D>) -> StyledObject<D> {
+    let look = look(severity);
+    let text = match look.colour {
+        Some(Colour::Red) => text.red(),
+        Some(Colour::Yellow) => text.yellow(),
+        Some(Colour::Cyan) => text.cyan(),
+        None => text,
+    };
+    match look.emphasis {
+        Emphasis::Bold => text.bold(),
+        Emphasis::Normal => text,
+        Emphasis::Dim => text.dim(),
+    }
+}
+
+fn write_log(log: &mut File, text: &StatusText) -> io::Result<()> {
+    writeln!(log, "{}", text.format())?;
+    log.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mavlink::MavHeader;
+    use std::time::UNIX_EPOCH;
+
+    /// A STATUSTEXT received `millis` after the start, with `chunk` as `id` and `chunk_seq`
+    fn frame(millis: u64, text: &str, chunk: Option<(u16, u8)>) -> Frame {
+        let severity = MavSeverity::MAV_SEVERITY_WARNING;
+        let mut chars: Vec<_> = text.chars().collect();
+        chars.resize(CHUNK_LEN, '\0');
+        let raw = chunk.map(|(id, seq)| {
+            // a MAVLink 2 frame with an unchecked checksum, the payload is all that is read
+            let mut raw = vec![0xfd, PAYLOAD_LEN as u8, 0, 0, 0, 1, 1, 253, 0, 0];
+            raw.push(severity as u8);
+            raw.extend(chars.iter().map(|c| *c as u8));
+            raw.extend_from_slice(&id.to_le_bytes());
+            raw.push(seq);
+            raw.extend_from_slice(&[0, 0]);
+            raw.into()
+        });
+        Frame {
+            header: MavHeader {
+                system_id: 1,
+                component_id: 1,
+                sequence: 0,
+            },
+            message: MavMessage::STATUSTEXT(STATUSTEXT_DATA {
+                severity,
+                text: chars,
+            }),
+            time: UNIX_EPOCH + Duration::from_millis(millis),
+            raw,
+        }
+    }
+
+    fn texts(assembler: &mut Assembler, frame: &Frame) -> Vec<String> {
+        assembler
+            .update(frame)
+            .into_iter()
+            .map(|text| text.text)
+            .collect()
+    }
+
+    #[test]
+    fn mavlink1_texts_filling_a_chunk_are_continued() {
+        let long = "PreArm: Compass not calibrated, run the calibration";
+        let (first, rest) = long.split_at(CHUNK_LEN);
+        let mut assembler = Assembler::default();
+
+        assert!(texts(&mut assembler, &frame(0, first, None)).is_empty());
+        assert_eq!(texts(&mut assembler, &frame(10, rest, None)), [long]);
+        assert_eq!(texts(&mut assembler, &frame(20, "short", None)), ["short"]);
+
+        // a chunk which is not continued in time is complete on its own
+        assert!(texts(&mut assembler, &frame(30, first, None)).is_empty());
+        let other = Frame {
+            message: MavMessage::HEARTBEAT(Default::default()),
+            ..frame(30 + 501, "", None)
+        };
+        assert_eq!(texts(&mut assembler, &other), [first]);
+    }
+
+    #[test]
+    fn mavlink2_chunks_are_joined_by_their_id() {
+        let a = "A".repeat(CHUNK_LEN);
+        let b = "B".repeat(CHUNK_LEN);
+        let mut assembler = Assembler::default();
+
+        // two texts with their chunks interleaved
+        assert!(texts(&mut assembler, &frame(0, &a, Some((1, 0)))).is_empty());
+        assert!(texts(&mut assembler, &frame(1, &b, Some((2, 0)))).is_empty());
+        assert_eq!(
+            texts(&mut assembler, &frame(2, "a", Some((1, 1)))),
+            [format!("{}a", a)]
+        );
+        assert_eq!(
+            texts(&mut assembler, &frame(3, "b", Some((2, 1)))),
+            [format!("{}b", b)]
+        );
+
+        // id 0 is a text of its own, even if it fills the chunk
+        assert_eq!(
+            texts(&mut assembler, &frame(4, &a, Some((0, 0)))),
+            [a.as_str()]
+        );
+
+        // a new first chunk completes the previous text with the same id
+        assert!(texts(&mut assembler, &frame(5, &a, Some((3, 0)))).is_empty());
+        assert_eq!(texts(&mut assembler, &frame(6, &b, Some((3, 0)))), [a]);
+    }
+}
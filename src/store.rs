@@ -0,0 +1,264 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::prelude::*;
+use mavlink::common::*;
+use skim::{prelude::*, DisplayContext, PreviewContext, SkimItem};
+
+use crate::{
+    mavlink_stub::{self, MavlinkConnectionHandler},
+    parameters::Parameter,
+    report::Session,
+    util::{self, *},
+};
+
+/// The parameters of a vehicle, kept up to date in the background
+///
+/// Every PARAM_VALUE message received updates the store, so values changed by other ground
+/// stations or echoed after a PARAM_SET show up without fetching all parameters again.
+#[derive(Clone)]
+pub struct ParameterStore {
+    inner: Arc<Mutex<Inner>>,
+}
+
+/// A parameter in the fuzzy finder whose preview always shows the latest value from the store
+#[derive(Clone)]
+pub struct LiveParameter {
+    snapshot: Parameter,
+    store: ParameterStore,
+}
+
+impl ParameterStore {
+    /// Start fetching all parameters from the vehicle in the background
+    ///
+    /// Parameters which got lost on the way are requested again individually.
+    pub fn fetch(conn: Arc<MavlinkConnectionHandler>) -> Self {
+        let store = ParameterStore {
+            inner: Arc::new(Mutex::new(Inner::default())),
+        };
+
+        util::spawn({
+            let store = store.clone();
+            move || async move { store.receive(&conn).await }
+        });
+
+        store
+    }
+
+    /// The receiving end of a channel for the fuzzy finder
+    ///
+    /// All parameters known so far are sent immediately, parameters arriving later are sent as
+    /// they arrive. The channel is closed once all parameters have been received.
+    pub fn items(&self) -> SkimItemReceiver {
+        let (tx_item, rx_item): (SkimItemSender, SkimItemReceiver) = unbounded();
+        let mut inner = self.inner.lock().unwrap();
+        for param in inner.parameters.values() {
+            let _ = tx_item.send(Arc::new(self.live(param.clone())));
+        }
+        // a previous session is replaced, as there can only be one fuzzy finder at a time
+        inner.sink = match inner.is_complete() {
+            true => None,
+            false => Some(tx_item),
+        };
+        rx_item
+    }
+
+    /// Like `items`, but only the given parameters in the given order
+    pub fn items_named(&self, names: &[String]) -> SkimItemReceiver {
+        let (tx_item, rx_item): (SkimItemSender, SkimItemReceiver) = unbounded();
+        for param in names.iter().filter_map(|name| self.get(name)) {
+            let _ = tx_item.send(Arc::new(self.live(param)));
+        }
+        rx_item
+    }
+
+    /// The latest state of a parameter
+    pub fn get(&self, name: &str) -> Option<Parameter> {
+        self.inner.lock().unwrap().parameters.get(name).cloned()
+    }
+
+    /// All parameters known so far, sorted by name
+    pub fn all(&self) -> Vec<Parameter> {
+        self.inner
+            .lock()
+            .unwrap()
+            .parameters
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Replace a parameter with a locally changed version
+    pub fn update(&self, param: Parameter) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.revision += 1;
+        inner.parameters.insert(param.name.clone(), param);
+    }
+
+    /// Changes whenever a value is received or updated, to notice changes cheaply
+    pub fn revision(&self) -> usize {
+        self.inner.lock().unwrap().revision
+    }
+
+    /// Whether all parameters reported by the vehicle have been received
+    pub fn is_complete(&self) -> bool {
+        self.inner.lock().unwrap().is_complete()
+    }
+
+    /// Number of parameters received so far and the number reported by the vehicle
+    pub fn progress(&self) -> (usize, usize) {
+        let inner = self.inner.lock().unwrap();
+        (inner.parameters.len(), inner.count)
+    }
+
+    /// Push a changed parameter to the vehicle, record the change in the session and update it
+    ///
+    /// `old` is the value the parameter had before. Returns whether the vehicle confirmed it.
+    pub async fn push(
+        &self,
+        conn: &MavlinkConnectionHandler,
+        param: Parameter,
+        old: f32,
+        session: &mut Session,
+    ) -> io::Result<bool> {
+        let confirmed = param.push_confirmed(conn).await?;
+        session.record(&param, Some(old), confirmed);
+        self.update(param);
+        Ok(confirmed)
+    }
+
+    /// Convert the selection of the fuzzy finder to the latest state of the parameters
+    pub fn resolve(&self, selection: Vec<LiveParameter>) -> Vec<Parameter> {
+        selection
+            .into_iter()
+            .map(|live| self.get(&live.snapshot.name).unwrap_or(live.snapshot))
+            .collect()
+    }
+
+    fn live(&self, snapshot: Parameter) -> LiveParameter {
+        LiveParameter {
+            snapshot,
+            store: self.clone(),
+        }
+    }
+
+    /// Receive PARAM_VALUE messages for as long as the program runs
+    async fn receive(&self, conn: &MavlinkConnectionHandler) {
+        let mut stream = conn
+            .subscribe(mavlink_stub::message_type(&MavMessage::PARAM_VALUE(
+                Default::default(),
+            )))
+            .await;
+
+        let request = MavMessage::PARAM_REQUEST_LIST(PARAM_REQUEST_LIST_DATA {
+            target_component: 0,
+            target_system: 0,
+        });
+        let _ = conn.send_default(&request);
+
+        let ttl = Duration::from_secs(2);
+        loop {
+            let next = futures::select_biased! {
+                message = stream.next().fuse() => message,
+                _ = futures::FutureExt::fuse(smol::Timer::after(ttl)) => None,
+            };
+
+            match next {
+                Some(MavMessage::PARAM_VALUE(data)) => self.insert(data),
+                Some(_) => {}
+                // the link went silent, ask again for the parameters we missed
+                None if self.inner.lock().unwrap().count == 0 => {
+                    let _ = conn.send_default(&request);
+                }
+                None => {
+                    for index in self.inner.lock().unwrap().missing() {
+                        let request = MavMessage::PARAM_REQUEST_READ(PARAM_REQUEST_READ_DATA {
+                            param_index: index as i16,
+                            target_system: 0,
+                            target_component: 0,
+                            param_id: to_char_arr(""),
+                        });
+                        let _ = conn.send_default(&request);
+                    }
+                }
+            }
+        }
+    }
+
+    fn insert(&self, data: PARAM_VALUE_DATA) {
+        let name = to_string(&data.param_id);
+        let mut inner = self.inner.lock().unwrap();
+        inner.revision += 1;
+        inner.count = inner.count.max(data.param_count as usize);
+        // answers to PARAM_REQUEST_READ by name carry the index -1 resp. 65535
+        if data.param_index < data.param_count {
+            inner.indices.insert(data.param_index);
+        }
+
+        match inner.parameters.get_mut(&name) {
+            Some(param) => {
+                param.value = data.param_value;
+                param.param_type = data.param_type;
+            }
+            None => {
+                let param =
+                    Parameter::from_vehicle(name.clone(), data.param_value, data.param_type);
+                if let Some(sink) = &inner.sink {
+                    let _ = sink.send(Arc::new(self.live(param.clone())));
+                }
+                inner.parameters.insert(name, param);
+            }
+        }
+
+        if inner.is_complete() {
+            // closes the channel, so that the fuzzy finder knows that there is nothing more
+            inner.sink = None;
+        }
+    }
+}
+
+impl SkimItem for LiveParameter {
+    fn display<'a>(&'a self, context: DisplayContext<'a>) -> AnsiString<'a> {
+        self.snapshot.display(context)
+    }
+
+    fn text(&self) -> Cow<'_, str> {
+        self.snapshot.text()
+    }
+
+    fn preview(&self, context: PreviewContext) -> ItemPreview {
+        match self.store.get(&self.snapshot.name) {
+            Some(param) => param.preview(context),
+            None => self.snapshot.preview(context),
+        }
+    }
+}
+
+// Implementation details
+
+#[derive(Default)]
+struct Inner {
+    parameters: BTreeMap<String, Parameter>,
+    /// `param_index` of all received parameters
+    indices: BTreeSet<u16>,
+    /// `param_count` as reported by the vehicle
+    count: usize,
+    /// Channel to the currently running fuzzy finder, if it still waits for items
+    sink: Option<SkimItemSender>,
+    /// Incremented with every change of a parameter
+    revision: usize,
+}
+
+impl Inner {
+    fn is_complete(&self) -> bool {
+        self.count > 0 && self.indices.len() >= self.count
+    }
+
+    fn missing(&self) -> Vec<u16> {
+        (0..self.count as u16)
+            .filter(|i| !self.indices.contains(i))
+            .collect()
+    }
+}
@@ -0,0 +1,317 @@
+use std::io;
+use std::time::Duration;
+
+use console::style;
+use futures::prelude::*;
+use mavlink::{common::*, Message};
+
+use crate::{
+    command,
+    mavlink_stub::{self, MavlinkConnectionHandler},
+    ui,
+    util::with_timeout,
+};
+
+// API
+
+/// Set the rate at which the vehicle sends messages
+///
+/// Each assignment is of the form NAME=RATE, where RATE is in Hz, `off` or `default`. With
+/// `legacy`, NAME is a data stream like `EXTRA1` and the rate is requested with
+/// REQUEST_DATA_STREAM, for firmware which does not support message intervals. Without it, the
+/// data stream containing the message is requested if the vehicle turns out not to support
+/// message intervals. Returns whether all rates were accepted.
+pub async fn set(
+    conn: &MavlinkConnectionHandler,
+    assignments: &[String],
+    legacy: bool,
+) -> io::Result<bool> {
+    let mut success = true;
+    for assignment in assignments {
+        let (name, rate) = parse_assignment(assignment)?;
+        let result = match legacy {
+            true => request_data_stream(conn, &name, rate),
+            false => match set_interval(conn, &name, rate).await {
+                Err(e) if e.kind() == io::ErrorKind::Unsupported => fall_back(conn, &name, rate),
+                result => result,
+            },
+        };
+        match result {
+            Ok(()) => println!("{}: {}", style(&name).bold(), rate),
+            Err(e) => {
+                success = false;
+                eprintln!("{}: {}", style(&name).bold(), style(e).red());
+            }
+        }
+    }
+    Ok(success)
+}
+
+/// Print the rate at which the vehicle sends messages
+///
+/// Returns whether the rate of all messages could be queried.
+pub async fn get(conn: &MavlinkConnectionHandler, names: &[String]) -> io::Result<bool> {
+    let mut success = true;
+    for name in names {
+        let name = name.to_uppercase();
+        match get_interval(conn, &name).await {
+            Ok(interval) => println!("{}: {}", style(&name).bold(), describe(interval)),
+            Err(e) => {
+                success = false;
+                eprintln!("{}: {}", style(&name).bold(), style(e).red());
+            }
+        }
+    }
+    Ok(success)
+}
+
+// Implementation details
+
+/// How long to wait for the MESSAGE_INTERVAL answering a query
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The requested rate of a message
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rate {
+    Hz(f32),
+    /// Stop sending the message
+    Off,
+    /// Return to the rate the firmware uses by default
+    Default,
+}
+
+impl std::fmt::Display for Rate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rate::Hz(hz) => write!(f, "{} Hz", hz),
+            Rate::Off => write!(f, "off"),
+            Rate::Default => write!(f, "default rate"),
+        }
+    }
+}
+
+fn parse_assignment(assignment: &str) -> io::Result<(String, Rate)> {
+    let invalid = |reason: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{:?} {}", assignment, reason),
+        )
+    };
+    let (name, rate) = assignment
+        .split_once('=')
+        .ok_or_else(|| invalid("is not of the form NAME=RATE"))?;
+    let rate = match rate.trim().to_lowercase().as_str() {
+        "off" => Rate::Off,
+        "default" => Rate::Default,
+        hz => match hz.parse::<f32>() {
+            Ok(0.0) => Rate::Off,
+            Ok(hz) if hz > 0.0 && hz.is_finite() => Rate::Hz(hz),
+            _ => return Err(invalid("has no valid rate, use Hz, `off` or `default`")),
+        },
+    };
+    Ok((name.trim().to_uppercase(), rate))
+}
+
+/// The id of a message by its name, e.g. `ATTITUDE`
+fn message_id(name: &str) -> io::Result<u32> {
+    MavMessage::message_id_from_name(name)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "not a known MAVLink message"))
+}
+
+async fn set_interval(conn: &MavlinkConnectionHandler, name: &str, rate: Rate) -> io::Result<()> {
+    // -1 disables a message, 0 restores its default interval
+    let interval = match rate {
+        Rate::Hz(hz) => 1e6 / hz,
+        Rate::Off => -1.0,
+        Rate::Default => 0.0,
+    };
+    let cmd = MavCmd::MAV_CMD_SET_MESSAGE_INTERVAL;
+    let result = command::send(
+        conn,
+        COMMAND_LONG_DATA {
+            command: cmd,
+            param1: message_id(name)? as f32,
+            param2: interval,
+            ..Default::default()
+        },
+    )
+    .await?;
+    match result {
+        MavResult::MAV_RESULT_UNSUPPORTED => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "message intervals are not supported by the vehicle",
+        )),
+        result => command::check(cmd, result),
+    }
+}
+
+/// Request the data stream containing a message, for firmware without message intervals
+fn fall_back(conn: &MavlinkConnectionHandler, name: &str, rate: Rate) -> io::Result<()> {
+    let (stream, messages) = STREAM_MESSAGES
+        .iter()
+        .find(|(_, messages)| messages.contains(&name))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "message intervals are not supported by the vehicle, and the message is part of \
+                 no data stream",
+            )
+        })?;
+    let others: Vec<_> = messages.iter().filter(|m| **m != name).copied().collect();
+    let mut warning = format!(
+        "message intervals are not supported by the vehicle, requesting the {} data stream instead",
+        stream
+    );
+    if !others.is_empty() {
+        warning += &format!(", which sets the rate of {} as well", others.join(", "));
+    }
+    ui::warning(&warning);
+    request_data_stream(conn, stream, rate)
+}
+
+/// Query the interval of a message in microseconds
+async fn get_interval(conn: &MavlinkConnectionHandler, name: &str) -> io::Result<i32> {
+    let id = message_id(name)?;
+    let stream = conn
+        .subscribe(mavlink_stub::message_type(&MavMessage::MESSAGE_INTERVAL(
+            Default::default(),
+        )))
+        .await;
+    let cmd = MavCmd::MAV_CMD_GET_MESSAGE_INTERVAL;
+    let result = command::send(
+        conn,
+        COMMAND_LONG_DATA {
+            command: cmd,
+            param1: id as f32,
+            ..Default::default()
+        },
+    )
+    .await?;
+    command::check(cmd, result)?;
+
+    with_timeout(TIMEOUT, "MESSAGE_INTERVAL", async {
+        let mut stream = stream;
+        loop {
+            match stream.next().await {
+                Some(MavMessage::MESSAGE_INTERVAL(data)) if u32::from(data.message_id) == id => {
+                    return data.interval_us;
+                }
+                _ => {}
+            }
+        }
+    })
+    .await
+}
+
+/// A message interval as reported in MESSAGE_INTERVAL
+fn describe(interval: i32) -> String {
+    match interval {
+        -1 => String::from("off"),
+        0 => String::from("not available"),
+        us => format!(
+            "{} Hz ({} µs)",
+            (1e6 / us as f64 * 100.0).round() / 100.0,
+            us
+        ),
+    }
+}
+
+/// Request a data stream, the predecessor of message intervals
+///
+/// REQUEST_DATA_STREAM is not acknowledged, so success can only be checked by watching the rate,
+/// e.g. with `stats`.
+fn request_data_stream(conn: &MavlinkConnectionHandler, name: &str, rate: Rate) -> io::Result<()> {
+    let name = name.trim_start_matches("MAV_DATA_STREAM_");
+    let stream = DATA_STREAMS
+        .iter()
+        .find(|(stream, _)| *stream == name)
+        .map(|(_, stream)| *stream)
+        .ok_or_else(|| {
+            let names: Vec<_> = DATA_STREAMS.iter().map(|(name, _)| *name).collect();
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown data stream, use one of {}", names.join(", ")),
+            )
+        })?;
+    let (rate, start) = match rate {
+        Rate::Hz(hz) => (hz.round().max(1.0) as u16, 1),
+        Rate::Off => (0, 0),
+        Rate::Default => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "data streams have no default rate",
+            ))
+        }
+    };
+    conn.send_default(&MavMessage::REQUEST_DATA_STREAM(REQUEST_DATA_STREAM_DATA {
+        req_message_rate: rate,
+        req_stream_id: stream as u8,
+        start_stop: start,
+        ..Default::default()
+    }))
+}
+
+/// The messages ArduPilot sends in each data stream
+const STREAM_MESSAGES: [(&str, &[&str]); 7] = [
+    (
+        "RAW_SENSORS",
+        &[
+            "RAW_IMU",
+            "SCALED_IMU2",
+            "SCALED_PRESSURE",
+            "SENSOR_OFFSETS",
+        ],
+    ),
+    (
+        "EXTENDED_STATUS",
+        &[
+            "SYS_STATUS",
+            "POWER_STATUS",
+            "MEMINFO",
+            "MISSION_CURRENT",
+            "GPS_RAW_INT",
+            "GPS2_RAW",
+            "NAV_CONTROLLER_OUTPUT",
+            "FENCE_STATUS",
+        ],
+    ),
+    ("POSITION", &["GLOBAL_POSITION_INT", "LOCAL_POSITION_NED"]),
+    (
+        "RC_CHANNELS",
+        &["SERVO_OUTPUT_RAW", "RC_CHANNELS", "RC_CHANNELS_RAW"],
+    ),
+    ("EXTRA1", &["ATTITUDE", "SIMSTATE", "AHRS2", "PID_TUNING"]),
+    ("EXTRA2", &["VFR_HUD"]),
+    (
+        "EXTRA3",
+        &[
+            "AHRS",
+            "HWSTATUS",
+            "SYSTEM_TIME",
+            "RANGEFINDER",
+            "BATTERY2",
+            "MOUNT_STATUS",
+            "EKF_STATUS_REPORT",
+            "VIBRATION",
+            "BATTERY_STATUS",
+        ],
+    ),
+];
+
+const DATA_STREAMS: [(&str, MavDataStream); 9] = [
+    ("ALL", MavDataStream::MAV_DATA_STREAM_ALL),
+    ("RAW_SENSORS", MavDataStream::MAV_DATA_STREAM_RAW_SENSORS),
+    (
+        "EXTENDED_STATUS",
+        MavDataStream::MAV_DATA_STREAM_EXTENDED_STATUS,
+    ),
+    ("RC_CHANNELS", MavDataStream::MAV_DATA_STREAM_RC_CHANNELS),
+    (
+        "RAW_CONTROLLER",
+        MavDataStream::MAV_DATA_STREAM_RAW_CONTROLLER,
+    ),
+    ("POSITION", MavDataStream::MAV_DATA_STREAM_POSITION),
+    ("EXTRA1", MavDataStream::MAV_DATA_STREAM_EXTRA1),
+    ("EXTRA2", MavDataStream::MAV_DATA_STREAM_EXTRA2),
+    ("EXTRA3", MavDataStream::MAV_DATA_STREAM_EXTRA3),
+];
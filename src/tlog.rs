@@ -0,0 +1,285 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mavlink::{common::MavMessage, error::MessageReadError, MavHeader, MavlinkVersion};
+
+use crate::mavlink_stub::Frame;
+
+// API
+
+/// Reads the frames of a telemetry log, see `Writer` for the format
+///
+/// Yields the frames with the time they were logged. Frames which can not be parsed are yielded
+/// as errors, those with a bad checksum as `InvalidData`, a truncated last frame ends the log. Bytes which do not start a frame are skipped up
+/// to the next frame and yielded as a single error.
+pub struct Reader {
+    reader: BufReader<File>,
+    /// The time and magic byte of the next frame, if found while skipping invalid bytes
+    next: Option<[u8; 9]>,
+}
+
+/// Writes frames to a telemetry log
+///
+/// A tlog is the raw MAVLink stream with every frame preceded by the time it was received or
+/// sent, as big-endian microseconds since the Unix epoch. This is the format written by ground
+/// stations like QGroundControl and Mission Planner.
+///
+/// Frames are written as they were received if their raw bytes are known, otherwise they are
+/// serialized again as MAVLink 1, which is the version spoken on live connections. Frames which
+/// can not be parsed are dropped by the connection before they could be recorded, so they are
+/// missing from the log.
+pub struct Writer {
+    file: File,
+}
+
+impl Reader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Ok(Reader {
+            reader: BufReader::new(File::open(path)?),
+            next: None,
+        })
+    }
+
+    /// Read the next frame, `None` at the end of the log
+    fn read(&mut self) -> Option<Result<Frame, MessageReadError>> {
+        let mut start = match self.next.take() {
+            Some(start) => start,
+            None => {
+                let mut start = [0; 9];
+                self.reader.read_exact(&mut start).ok()?;
+                start
+            }
+        };
+        if !is_magic(start[8]) {
+            // slide over the invalid bytes until a magic byte follows a time
+            let magic = start[8];
+            let mut skipped = 0;
+            while !is_magic(start[8]) {
+                start.copy_within(1.., 0);
+                self.reader.read_exact(&mut start[8..]).ok()?;
+                skipped += 1;
+            }
+            self.next = Some(start);
+            return Some(Err(MessageReadError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{:#04x} is not the start of a MAVLink frame, skipped {} bytes",
+                    magic, skipped
+                ),
+            ))));
+        }
+        let mut micros = [0; 8];
+        micros.copy_from_slice(&start[..8]);
+        let micros = u64::from_be_bytes(micros);
+        let time = UNIX_EPOCH + Duration::from_micros(micros);
+
+        // read the whole frame first, so that a corrupt frame does not throw off the next one
+        let mut frame = vec![start[8], 0];
+        self.reader.read_exact(&mut frame[1..]).ok()?;
+        let (version, len) = match frame[0] {
+            V1_MAGIC => (MavlinkVersion::V1, V1_OVERHEAD + frame[1] as usize),
+            V2_MAGIC => {
+                let mut flags = [0];
+                self.reader.read_exact(&mut flags).ok()?;
+                frame.push(flags[0]);
+                let signature = match flags[0] & V2_SIGNED {
+                    0 => 0,
+                    _ => V2_SIGNATURE,
+                };
+                (
+                    MavlinkVersion::V2,
+                    V2_OVERHEAD + frame[1] as usize + signature,
+                )
+            }
+            _ => unreachable!("the magic byte was checked"),
+        };
+        let start = frame.len();
+        frame.resize(len, 0);
+        self.reader.read_exact(&mut frame[start..]).ok()?;
+
+        let parsed = mavlink::read_versioned_msg(&mut frame.as_slice(), version).map_err(|e| {
+            match e {
+                // the frame is complete, mavlink only reads past it if the checksum is wrong
+                MessageReadError::Io(_) => MessageReadError::Io(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "frame with a bad checksum",
+                )),
+                e => e,
+            }
+        });
+        Some(parsed.map(|(header, message)| Frame {
+            header,
+            message,
+            time,
+            raw: Some(frame.into()),
+        }))
+    }
+}
+
+impl Iterator for Reader {
+    type Item = Result<Frame, MessageReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read()
+    }
+}
+
+impl Writer {
+    /// Create a new tlog, replacing an existing file
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        Ok(Writer { file })
+    }
+
+    /// Append a frame
+    ///
+    /// Each frame is written at once, so that the log is complete up to the last frame even if
+    /// the program is killed.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw` - the frame as it was received, if known, which is written instead of `message`
+    pub fn write(
+        &mut self,
+        time: SystemTime,
+        header: MavHeader,
+        message: &MavMessage,
+        raw: Option<&[u8]>,
+    ) -> io::Result<()> {
+        let micros = time
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_micros() as u64);
+        let mut record = micros.to_be_bytes().to_vec();
+        match raw {
+            Some(raw) => record.extend_from_slice(raw),
+            None => {
+                mavlink::write_versioned_msg(&mut record, MavlinkVersion::V1, header, message)?;
+            }
+        }
+        self.file.write_all(&record)
+    }
+}
+
+/// The payload of a raw MAVLink 2 frame, `None` for other frames
+///
+/// MAVLink 2 drops the trailing zeros of a payload, they are restored up to `len` bytes.
+pub fn v2_payload(raw: &[u8], len: usize) -> Option<Vec<u8>> {
+    if raw.first() != Some(&V2_MAGIC) {
+        return None;
+    }
+    let end = V2_HEADER + *raw.get(1)? as usize;
+    let mut payload = raw.get(V2_HEADER..end)?.to_vec();
+    if payload.len() < len {
+        payload.resize(len, 0);
+    }
+    Some(payload)
+}
+
+// Implementation details
+
+const V1_MAGIC: u8 = 0xfe;
+const V2_MAGIC: u8 = 0xfd;
+
+/// Length of a MAVLink 1 frame besides its payload
+const V1_OVERHEAD: usize = 8;
+
+/// Length of a MAVLink 2 frame besides its payload and signature
+const V2_OVERHEAD: usize = 12;
+
+/// Length of the header of a MAVLink 2 frame, up to the payload
+const V2_HEADER: usize = 10;
+
+fn is_magic(byte: u8) -> bool {
+    byte == V1_MAGIC || byte == V2_MAGIC
+}
+
+/// Incompatibility flag of a signed MAVLink 2 frame
+const V2_SIGNED: u8 = 0x01;
+
+/// Length of the signature of a MAVLink 2 frame
+const V2_SIGNATURE: usize = 13;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mavlink::common::{HEARTBEAT_DATA, PARAM_VALUE_DATA};
+
+    fn header(sequence: u8) -> MavHeader {
+        MavHeader {
+            system_id: 1,
+            component_id: 1,
+            sequence,
+        }
+    }
+
+    #[test]
+    fn frames_survive_a_round_trip() {
+        let path = std::env::temp_dir().join(format!("mavlink-cli-{}.tlog", std::process::id()));
+        let heartbeat = MavMessage::HEARTBEAT(HEARTBEAT_DATA::default());
+        let param = MavMessage::PARAM_VALUE(PARAM_VALUE_DATA {
+            param_value: 1.5,
+            param_count: 1,
+            ..Default::default()
+        });
+        let mut raw = Vec::new();
+        mavlink::write_versioned_msg(&mut raw, MavlinkVersion::V2, header(1), &param).unwrap();
+        let time = UNIX_EPOCH + Duration::from_micros(1_600_000_000_000_000);
+
+        let mut writer = Writer::create(&path).unwrap();
+        writer.write(time, header(0), &heartbeat, None).unwrap();
+        writer.file.write_all(&[0xff, 0x00, 0x42]).unwrap();
+        writer.write(time, header(1), &param, Some(&raw)).unwrap();
+        drop(writer);
+
+        let frames: Vec<_> = Reader::open(&path).unwrap().collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(frames.len(), 3);
+        let first = frames[0].as_ref().unwrap();
+        assert_eq!(
+            (first.time, first.header, &first.message),
+            (time, header(0), &heartbeat)
+        );
+        assert!(frames[1].is_err());
+        let last = frames[2].as_ref().unwrap();
+        assert_eq!(
+            (last.time, last.header, &last.message),
+            (time, header(1), &param)
+        );
+        assert_eq!(last.raw.as_deref(), Some(raw.as_slice()));
+    }
+
+    #[test]
+    fn frames_with_a_bad_checksum_are_errors() {
+        let path =
+            std::env::temp_dir().join(format!("mavlink-cli-{}-crc.tlog", std::process::id()));
+        let heartbeat = MavMessage::HEARTBEAT(HEARTBEAT_DATA::default());
+        let mut corrupt = Vec::new();
+        mavlink::write_versioned_msg(&mut corrupt, MavlinkVersion::V1, header(0), &heartbeat)
+            .unwrap();
+        corrupt[7] ^= 0xff;
+        let time = UNIX_EPOCH;
+
+        let mut writer = Writer::create(&path).unwrap();
+        writer
+            .write(time, header(0), &heartbeat, Some(&corrupt))
+            .unwrap();
+        writer.write(time, header(1), &heartbeat, None).unwrap();
+        drop(writer);
+
+        let frames: Vec<_> = Reader::open(&path).unwrap().collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(frames.len(), 2);
+        match &frames[0] {
+            Err(MessageReadError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+            _ => panic!("expected an error about the checksum"),
+        }
+        assert_eq!(frames[1].as_ref().unwrap().header, header(1));
+    }
+}
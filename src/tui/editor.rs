@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+
+use mavlink::common::MavParamType;
+use tuikit::prelude::*;
+
+use crate::definitions::{bitmask, DataType, Definition};
+
+/// An inline editor for the value of a parameter, depending on its `DataType`
+pub enum Editor {
+    /// Free text entry, used for ranges and parameters without a known data type
+    Text {
+        buffer: String,
+        /// Why the entered value violates the definition, shown until the next edit
+        problems: Vec<String>,
+    },
+    /// Choose one of the documented values
+    Values {
+        options: Vec<(i64, String)>,
+        cursor: usize,
+    },
+    /// Toggle the bits of a bitmask
+    Bitmask {
+        labels: BTreeMap<i64, String>,
+        bits: u64,
+        param_type: MavParamType,
+        cursor: u32,
+    },
+}
+
+/// The result of handling a key in an editor
+pub enum Outcome {
+    /// The editor is still open
+    Pending,
+    /// The user accepted a new value
+    Done(f32),
+    Cancel,
+}
+
+/// A line to draw, with its attributes
+pub type Line = (String, Attr);
+
+impl Editor {
+    pub fn new(def: &Definition, value: f32, param_type: MavParamType) -> Self {
+        match &def.data {
+            Some(DataType::Values(values)) if !values.is_empty() => {
+                let options: Vec<_> = values.iter().map(|(k, v)| (*k, v.clone())).collect();
+                let cursor = options
+                    .iter()
+                    .position(|(k, _)| (*k as f32 - value).abs() < 0.5)
+                    .unwrap_or_default();
+                Editor::Values { options, cursor }
+            }
+            Some(DataType::Bitmask(labels)) => Editor::Bitmask {
+                labels: labels.clone(),
+                bits: bitmask::to_bits(value, param_type),
+                param_type,
+                cursor: 0,
+            },
+            _ => Editor::text(value),
+        }
+    }
+
+    fn text(value: f32) -> Self {
+        Editor::Text {
+            buffer: value.to_string(),
+            problems: Vec::new(),
+        }
+    }
+
+    /// The value currently entered or selected, if it is a number
+    fn value(&self) -> Option<f32> {
+        match self {
+            Editor::Text { buffer, .. } => {
+                buffer.trim().parse().ok().filter(|v: &f32| v.is_finite())
+            }
+            Editor::Values { options, cursor } => options.get(*cursor).map(|(k, _)| *k as f32),
+            Editor::Bitmask {
+                bits, param_type, ..
+            } => Some(bitmask::from_bits(*bits, *param_type)),
+        }
+    }
+
+    /// Handle a key press
+    ///
+    /// Values violating the definition are explained and only accepted if [Enter] is pressed
+    /// again without changing them.
+    pub fn handle(&mut self, key: Key, def: &Definition, param_type: MavParamType) -> Outcome {
+        match key {
+            Key::ESC => return Outcome::Cancel,
+            // switch to free text entry, e.g. to enter an undocumented value
+            Key::Tab if !matches!(self, Editor::Text { .. }) => {
+                *self = Editor::text(self.value().unwrap_or_default());
+                return Outcome::Pending;
+            }
+            _ => {}
+        }
+
+        match self {
+            Editor::Text { buffer, problems } => match key {
+                Key::Char(c) => {
+                    buffer.push(c);
+                    problems.clear();
+                }
+                Key::Backspace | Key::Ctrl('h') => {
+                    buffer.pop();
+                    problems.clear();
+                }
+                Key::Enter => {
+                    let value = match buffer.trim().parse::<f32>() {
+                        Ok(value) if value.is_finite() => value,
+                        _ => {
+                            *problems = vec![format!("{:?} is not a number", buffer)];
+                            return Outcome::Pending;
+                        }
+                    };
+                    if !problems.is_empty() {
+                        return Outcome::Done(value);
+                    }
+                    *problems = def
+                        .violations(value, Some(param_type))
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect();
+                    if problems.is_empty() {
+                        return Outcome::Done(value);
+                    }
+                }
+                _ => {}
+            },
+            Editor::Values { options, cursor } => match key {
+                Key::Up | Key::Char('k') => *cursor = cursor.saturating_sub(1),
+                Key::Down | Key::Char('j') => *cursor = (*cursor + 1).min(options.len() - 1),
+                Key::Enter => return Outcome::Done(options[*cursor].0 as f32),
+                _ => {}
+            },
+            Editor::Bitmask {
+                bits,
+                param_type,
+                cursor,
+                ..
+            } => {
+                let width = bitmask::width(*param_type);
+                match key {
+                    Key::Up | Key::Char('k') => {
+                        *cursor = cursor.checked_sub(1).unwrap_or(width - 1)
+                    }
+                    Key::Down | Key::Char('j') => *cursor = (*cursor + 1) % width,
+                    Key::Char(' ') => *bits ^= 1 << *cursor,
+                    Key::Enter if bitmask::exact(*bits, *param_type) => {
+                        return Outcome::Done(bitmask::from_bits(*bits, *param_type))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Outcome::Pending
+    }
+
+    /// The lines to draw and the index of the line to keep visible
+    pub fn lines(&self) -> (Vec<Line>, usize) {
+        let plain = Attr::default();
+        let bold = Attr::from(Effect::BOLD);
+        let selected = Attr {
+            effect: Effect::REVERSE,
+            ..Attr::default()
+        };
+        let warning = Attr::from(Color::YELLOW);
+
+        match self {
+            Editor::Text { buffer, problems } => {
+                let mut lines = vec![(format!("> {}_", buffer), bold)];
+                for problem in problems {
+                    lines.push((format!("warning: {}", problem), warning));
+                }
+                if !problems.is_empty() {
+                    lines.push((String::from("press [Enter] again to apply it anyway"), bold));
+                }
+                (lines, 0)
+            }
+            Editor::Values { options, cursor } => {
+                let lines = options
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (k, v))| {
+                        let attr = if i == *cursor { selected } else { plain };
+                        (format!("{:>4} {}", k, v), attr)
+                    })
+                    .collect();
+                (lines, *cursor)
+            }
+            Editor::Bitmask {
+                labels,
+                bits,
+                param_type,
+                cursor,
+            } => {
+                let mut lines = vec![
+                    (
+                        format!("value {}", bitmask::from_bits(*bits, *param_type)),
+                        bold,
+                    ),
+                    (format!("hex   {}", bitmask::hex(*bits, *param_type)), plain),
+                    (
+                        format!("bin   {}", bitmask::binary(*bits, *param_type)),
+                        plain,
+                    ),
+                ];
+                if !bitmask::exact(*bits, *param_type) {
+                    let warning = Attr::from(Color::RED);
+                    lines.push((bitmask::inexact_warning(*bits, *param_type), warning));
+                }
+                lines.push((String::new(), plain));
+                let header = lines.len();
+                for bit in 0..bitmask::width(*param_type) {
+                    let label = labels
+                        .get(&i64::from(bit))
+                        .cloned()
+                        .unwrap_or_else(|| String::from("(unknown)"));
+                    let checked = if bits >> bit & 1 == 1 { 'x' } else { ' ' };
+                    let attr = if bit == *cursor { selected } else { plain };
+                    lines.push((format!("[{}] {:2} {}", checked, bit, label), attr));
+                }
+                (lines, header + *cursor as usize)
+            }
+        }
+    }
+
+    /// Keys available in this editor
+    pub fn help(&self) -> &'static str {
+        match self {
+            Editor::Text { .. } => "[enter] apply  [esc] cancel",
+            Editor::Values { .. } => "[↑↓] choose  [enter] apply  [tab] custom value  [esc] cancel",
+            Editor::Bitmask { .. } => {
+                "[↑↓] move  [space] toggle  [enter] apply  [tab] custom value  [esc] cancel"
+            }
+        }
+    }
+}
@@ -0,0 +1,840 @@
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use skim::SkimItem;
+use tuikit::prelude::*;
+
+use crate::{
+    definitions::{DataType, Definition},
+    groups::{Entry, Group},
+    history::History,
+    mavlink_stub::MavlinkConnectionHandler,
+    parameters::Parameter,
+    recent::Recent,
+    report::Session,
+    rules::{self, Rule, Violation},
+    staging::Staging,
+    statustext::{self, Console},
+    store::ParameterStore,
+};
+
+mod editor;
+
+use editor::{Editor, Line, Outcome};
+
+// API
+
+/// Run the full-screen configuration management
+///
+/// The parameter list on the left can be searched by typing, the side panel on the right shows
+/// the definition and current value of the selected parameter. Edits are staged and applied
+/// together with [CTRL+S], so that parameters depending on each other change at once. The query
+/// and the selected parameter are restored from `recent` and stored there again when leaving.
+/// With `grouped`, the list shows the tree of parameter groups, [Return] opens a group and
+/// [Backspace] leaves it again. With `messages`, the latest status texts of the vehicle are
+/// shown below the parameters.
+pub async fn run(
+    conn: &MavlinkConnectionHandler,
+    store: &ParameterStore,
+    rules: &[Rule],
+    grouped: bool,
+    recent: &mut Recent,
+    messages: Option<&Mutex<Console>>,
+    session: &mut Session,
+) -> io::Result<()> {
+    let term: Term<()> = Term::new().map_err(to_io)?;
+    let mut app = App::new(store, rules, grouped, recent);
+    app.messages = messages;
+
+    // the screen is only drawn again once something changed
+    let mut outdated = true;
+    loop {
+        outdated |= app.refresh(conn.last_heartbeat().await.map(|t| t.elapsed()));
+        if outdated {
+            app.draw(&term).map_err(to_io)?;
+            outdated = false;
+        }
+
+        let key = match term.peek_event(REFRESH) {
+            Ok(Event::Key(key)) => key,
+            Ok(Event::Resize { .. }) => {
+                outdated = true;
+                continue;
+            }
+            _ => continue,
+        };
+        outdated = true;
+        match app.handle(key) {
+            Command::None => {}
+            Command::Quit => break,
+            Command::Commit => app.commit(conn, session).await?,
+            Command::Undo => {
+                let edit = app.history.undo().map(|e| (e.name.clone(), e.old));
+                if app.revert(conn, session, edit, "undid").await? {
+                    app.history.undone();
+                }
+            }
+            Command::Redo => {
+                let edit = app.history.redo().map(|e| (e.name.clone(), e.new));
+                if app.revert(conn, session, edit, "redid").await? {
+                    app.history.redone();
+                }
+            }
+        }
+    }
+
+    app.recent.query = app.query.clone();
+    app.recent.selected = app.selected().map(|param| param.name);
+    Ok(())
+}
+
+// Implementation details
+
+/// How often changes are looked for while there is no input, to show live values
+const REFRESH: Duration = Duration::from_millis(100);
+
+/// The link is considered lost if no HEARTBEAT arrived for this long
+const LINK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Number of status texts shown in the messages pane
+const MESSAGES: usize = 4;
+
+const KEYS: &str = "[enter] edit  [ctrl-s] commit  [ctrl-d] discard  [ctrl-z/y] undo/redo  \
+                    [ctrl-r] recently edited  [esc] quit";
+
+const GROUPED_KEYS: &str = "[enter] edit/open  [bksp] leave group  [ctrl-s] commit  \
+                            [ctrl-d] discard  [ctrl-z/y] undo/redo  [ctrl-r] recently edited  \
+                            [esc] quit";
+
+/// What the event loop has to do after a key press
+enum Command {
+    None,
+    Quit,
+    Commit,
+    Undo,
+    Redo,
+}
+
+struct App<'a> {
+    store: &'a ParameterStore,
+    rules: &'a [Rule],
+    staging: Staging,
+    history: History,
+    recent: &'a mut Recent,
+    query: String,
+    /// The parameter to select once it arrives, as it was selected in the last run
+    restore: Option<String>,
+    /// Whether the parameters are browsed in the tree of their groups
+    grouped: bool,
+    /// Path of the group being browsed, empty for the top level
+    group: String,
+    /// The tree of parameter groups, while browsing it
+    tree: Option<Arc<Group<Parameter>>>,
+    /// Names of the parameters matching the query, or the keys of the entries of the group
+    matches: Vec<String>,
+    /// Number of parameters in the store when `matches` was computed
+    indexed: usize,
+    /// Revision of the store when the rules were checked
+    revision: usize,
+    /// Whether the rules have to be checked again, as the staged edits changed
+    unchecked: bool,
+    /// Number of status texts received when the messages were drawn
+    texts: Option<usize>,
+    /// Total number of parameters reported by the vehicle
+    count: usize,
+    cursor: usize,
+    /// First row of `matches` shown in the list
+    scroll: Cell<usize>,
+    /// The parameter being edited
+    editor: Option<(Parameter, Definition, Editor)>,
+    violations: Vec<Violation>,
+    /// Time since the last HEARTBEAT, if one was received
+    link: Option<Duration>,
+    message: Option<(String, Color)>,
+    /// The status texts of the vehicle, if they are shown
+    messages: Option<&'a Mutex<Console>>,
+    /// Whether quitting was requested while there were pending changes
+    quitting: bool,
+}
+
+impl<'a> App<'a> {
+    fn new(
+        store: &'a ParameterStore,
+        rules: &'a [Rule],
+        grouped: bool,
+        recent: &'a mut Recent,
+    ) -> Self {
+        App {
+            store,
+            rules,
+            staging: Staging::new(),
+            history: History::new(),
+            query: recent.query.clone(),
+            restore: recent.selected.clone(),
+            recent,
+            grouped,
+            group: String::new(),
+            tree: None,
+            matches: Vec::new(),
+            indexed: 0,
+            revision: 0,
+            unchecked: true,
+            texts: None,
+            count: 0,
+            cursor: 0,
+            scroll: Cell::new(0),
+            editor: None,
+            violations: Vec::new(),
+            link: None,
+            message: None,
+            messages: None,
+            quitting: false,
+        }
+    }
+
+    /// Pick up newly received parameters and status texts, and the state of the link
+    ///
+    /// Returns whether anything shown changed. The rules are only checked again if values
+    /// changed.
+    fn refresh(&mut self, link: Option<Duration>) -> bool {
+        let (received, count) = self.store.progress();
+        let revision = self.store.revision();
+        let texts = self
+            .messages
+            .map(|console| console.lock().unwrap().received);
+        let outdated = revision != self.revision
+            || count != self.count
+            || texts != self.texts
+            || link_status(link) != link_status(self.link);
+        self.count = count;
+        self.link = link;
+        self.texts = texts;
+        if received != self.indexed {
+            self.search();
+        }
+        if revision != self.revision || self.unchecked {
+            self.violations = rules::check(self.rules, &self.staging.overlay(self.store.all()));
+            self.revision = revision;
+            self.unchecked = false;
+        }
+        outdated
+    }
+
+    /// Find all parameters matching the query
+    ///
+    /// The query is matched fuzzily like in the fuzzy finder, every word of it has to match the
+    /// name, display name or description, and the best matches come first. With
+    /// `recent::FILTER` in the query, only the recently edited parameters are searched, the most
+    /// recent one first. While browsing groups, the entries of the current group are listed
+    /// unless there is a query, which searches all parameters within the group.
+    fn search(&mut self) {
+        let (only_recent, query) = Recent::filter(&self.query);
+        self.indexed = self.store.progress().0;
+        self.tree = None;
+        let parameters = match only_recent {
+            true => self
+                .recent
+                .names()
+                .iter()
+                .filter_map(|name| self.store.get(name))
+                .collect(),
+            false if self.grouped => {
+                let tree = Arc::new(Group::tree(&self.store.all()));
+                let group = tree.find(&self.group).unwrap_or_else(|| tree.clone());
+                self.tree = Some(tree);
+                match query.trim().is_empty() {
+                    true => {
+                        let entries = group.entries(group.path.is_empty());
+                        self.matches = entries.iter().map(Entry::key).collect();
+                        self.settle();
+                        return;
+                    }
+                    false => group.all_items(),
+                }
+            }
+            false => self.store.all(),
+        };
+
+        let matcher = SkimMatcherV2::default();
+        let terms: Vec<_> = query.split_whitespace().collect();
+        let mut scored: Vec<_> = parameters
+            .into_iter()
+            .filter_map(|param| {
+                let text = param.text();
+                let score: Option<i64> = terms
+                    .iter()
+                    .map(|term| matcher.fuzzy_match(&text, term))
+                    .sum();
+                score.map(|score| (score, param.name))
+            })
+            .collect();
+        // the sort is stable, so equally good matches stay in their order
+        scored.sort_by_key(|(score, _)| Reverse(*score));
+        self.matches = scored.into_iter().map(|(_, name)| name).collect();
+        self.settle();
+    }
+
+    /// Keep the cursor within the matches, and on the parameter to restore if it is there
+    fn settle(&mut self) {
+        self.cursor = self.cursor.min(self.matches.len().saturating_sub(1));
+
+        if let Some(position) = self
+            .restore
+            .as_ref()
+            .and_then(|name| self.matches.iter().position(|m| m == name))
+        {
+            self.cursor = position;
+            self.restore = None;
+        }
+    }
+
+    /// The latest state of a parameter, including staged edits
+    fn get(&self, name: &str) -> Option<Parameter> {
+        self.staging
+            .get(name)
+            .cloned()
+            .or_else(|| self.store.get(name))
+    }
+
+    /// The group an entry key like `EK3_/SRC1/` refers to, while browsing groups
+    fn group_at(&self, key: &str) -> Option<Arc<Group<Parameter>>> {
+        let path = key.strip_suffix('/')?;
+        self.tree.as_ref()?.find(path)
+    }
+
+    fn selected(&self) -> Option<Parameter> {
+        self.matches
+            .get(self.cursor)
+            .and_then(|name| self.get(name))
+    }
+
+    fn handle(&mut self, key: Key) -> Command {
+        if let Some((param, def, editor)) = &mut self.editor {
+            match editor.handle(key, def, param.param_type) {
+                Outcome::Pending => {}
+                Outcome::Cancel => self.editor = None,
+                Outcome::Done(value) => {
+                    let mut param = param.clone();
+                    let on_vehicle = self.store.get(&param.name).map_or(param.value, |p| p.value);
+                    param.value = value;
+                    self.staging.stage(param, on_vehicle);
+                    self.unchecked = true;
+                    self.editor = None;
+                }
+            }
+            return Command::None;
+        }
+
+        if !matches!(key, Key::ESC | Key::Ctrl('c')) {
+            self.quitting = false;
+        }
+        // the user took over before the parameter selected last time arrived
+        self.restore = None;
+        let page = 10;
+        let last = self.matches.len().saturating_sub(1);
+        match key {
+            Key::Up => self.cursor = self.cursor.saturating_sub(1),
+            Key::Down => self.cursor = (self.cursor + 1).min(last),
+            Key::PageUp => self.cursor = self.cursor.saturating_sub(page),
+            Key::PageDown => self.cursor = (self.cursor + page).min(last),
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = last,
+            Key::Char(c) => {
+                self.query.push(c);
+                self.search();
+            }
+            Key::Backspace | Key::Ctrl('h') if self.query.is_empty() => self.leave(),
+            Key::Backspace | Key::Ctrl('h') => {
+                self.query.pop();
+                self.search();
+            }
+            Key::Enter => self.enter(),
+            Key::Ctrl('r') => {
+                self.query = Recent::toggle(&self.query);
+                self.search();
+            }
+            Key::Ctrl('s') if !self.staging.is_empty() => return Command::Commit,
+            Key::Ctrl('d') => {
+                let discarded = self.staging.take().len();
+                self.unchecked = true;
+                self.message = Some((format!("discarded {} changes", discarded), Color::YELLOW));
+            }
+            Key::Ctrl('z') => return Command::Undo,
+            Key::Ctrl('y') => return Command::Redo,
+            Key::ESC if !self.query.is_empty() => {
+                self.query.clear();
+                self.search();
+            }
+            Key::ESC | Key::Ctrl('c') if self.staging.is_empty() || self.quitting => {
+                return Command::Quit
+            }
+            Key::ESC | Key::Ctrl('c') => {
+                self.quitting = true;
+                self.message = Some((
+                    format!(
+                        "{} changes are not committed yet, [ctrl-s] commits them, [esc] quits anyway",
+                        self.staging.len()
+                    ),
+                    Color::YELLOW,
+                ));
+            }
+            _ => {}
+        }
+        Command::None
+    }
+
+    /// Open the selected group, or edit the selected parameter
+    fn enter(&mut self) {
+        let key = match self.matches.get(self.cursor) {
+            Some(key) => key.clone(),
+            None => return,
+        };
+        if key == ".." {
+            self.leave();
+        } else if let Some(group) = self.group_at(&key) {
+            self.group = group.path.clone();
+            self.cursor = 0;
+            self.search();
+        } else {
+            self.edit();
+        }
+    }
+
+    /// Go back to the parent of the group being browsed, selecting the group left
+    fn leave(&mut self) {
+        if self.group.is_empty() {
+            return;
+        }
+        let left = format!("{}/", self.group);
+        self.group = match self.group.rsplit_once('/') {
+            Some((parent, _)) => parent.to_string(),
+            None => String::new(),
+        };
+        self.restore = Some(left);
+        self.search();
+    }
+
+    fn edit(&mut self) {
+        let param = match self.selected() {
+            Some(param) => param,
+            None => return,
+        };
+        let def = param.definition();
+        if def.read_only {
+            self.message = Some((format!("{} is read only", param.name), Color::RED));
+            return;
+        }
+        let editor = Editor::new(&def, param.value, param.param_type);
+        self.editor = Some((param, def, editor));
+    }
+
+    /// Apply the staged edits to the vehicle one at a time, keeping the failed ones staged
+    async fn commit(
+        &mut self,
+        conn: &MavlinkConnectionHandler,
+        session: &mut Session,
+    ) -> io::Result<()> {
+        let mut failed = Vec::new();
+        let mut applied = 0;
+        for (old, param) in self.staging.pending() {
+            self.recent.edited(&param.name);
+            let (name, value) = (param.name.clone(), param.value);
+            match self.store.push(conn, param, old, session).await {
+                Ok(true) => {
+                    self.staging.unstage(&name);
+                    self.history.record(&name, old, value);
+                    applied += 1;
+                }
+                Ok(false) => failed.push(name),
+                Err(e) => failed.push(format!("{} ({})", name, e)),
+            }
+        }
+        self.message = Some(match failed.as_slice() {
+            [] => (format!("committed {} changes", applied), Color::GREEN),
+            names => (
+                format!(
+                    "committed {} changes, {} stay staged: {}",
+                    applied,
+                    names.len(),
+                    names.join(", ")
+                ),
+                Color::RED,
+            ),
+        });
+        Ok(())
+    }
+
+    /// Set a parameter back to a previous value, for undo and redo
+    ///
+    /// Returns whether the vehicle confirmed the value.
+    async fn revert(
+        &mut self,
+        conn: &MavlinkConnectionHandler,
+        session: &mut Session,
+        edit: Option<(String, f32)>,
+        what: &str,
+    ) -> io::Result<bool> {
+        let (name, value) = match edit {
+            Some(edit) => edit,
+            None => {
+                self.message = Some((String::from("nothing to undo or redo"), Color::YELLOW));
+                return Ok(false);
+            }
+        };
+        let mut param = self
+            .store
+            .get(&name)
+            .unwrap_or_else(|| Parameter::new(name.clone(), value));
+        let old = param.value;
+        param.value = value;
+        self.recent.edited(&name);
+        let confirmed = self.store.push(conn, param, old, session).await?;
+        self.message = Some(match confirmed {
+            true => (format!("{} {} = {}", what, name, value), Color::GREEN),
+            false => (
+                format!(
+                    "the vehicle did not confirm {} = {}, the history is unchanged",
+                    name, value
+                ),
+                Color::RED,
+            ),
+        });
+        Ok(confirmed)
+    }
+
+    fn draw(&self, term: &Term<()>) -> tuikit::Result<()> {
+        let search = SearchBar(self);
+        let list = List(self);
+        let side = Side(self);
+        let status = Status(self);
+        let messages = Messages(self);
+
+        let side_title = match &self.editor {
+            Some((param, ..)) => format!(" edit {} ", param.name),
+            None => String::from(" definition "),
+        };
+        let list_title = match self.group.as_str() {
+            "" => format!(" parameters {}/{} ", self.matches.len(), self.indexed),
+            group => format!(" {}/ ", group),
+        };
+        let root = VSplit::default()
+            .split(Win::new(&search).basis(1).grow(0).shrink(0))
+            .split(
+                HSplit::default()
+                    .split(Win::new(&list).border(true).title(list_title))
+                    .split(Win::new(&side).border(true).title(side_title)),
+            );
+        let root = match self.messages {
+            Some(_) => root.split(
+                Win::new(&messages)
+                    .border(true)
+                    .title(" messages ")
+                    .basis(MESSAGES + 2)
+                    .grow(0)
+                    .shrink(0),
+            ),
+            None => root,
+        }
+        .split(Win::new(&status).basis(2).grow(0).shrink(0));
+
+        term.clear()?;
+        term.draw(&root)?;
+        term.present()
+    }
+}
+
+struct SearchBar<'a, 'b>(&'b App<'a>);
+struct List<'a, 'b>(&'b App<'a>);
+struct Side<'a, 'b>(&'b App<'a>);
+struct Status<'a, 'b>(&'b App<'a>);
+struct Messages<'a, 'b>(&'b App<'a>);
+
+impl Draw for SearchBar<'_, '_> {
+    fn draw(&self, canvas: &mut dyn Canvas) -> tuikit::Result<()> {
+        let prompt = "search> ";
+        canvas.print_with_attr(0, 0, prompt, Color::CYAN.into())?;
+        canvas.print(0, prompt.len(), &self.0.query)?;
+        canvas.set_cursor(0, prompt.len() + self.0.query.chars().count())?;
+        canvas.show_cursor(self.0.editor.is_none())
+    }
+}
+
+impl Draw for List<'_, '_> {
+    fn draw(&self, canvas: &mut dyn Canvas) -> tuikit::Result<()> {
+        let app = self.0;
+        let (width, height) = canvas.size()?;
+        if height == 0 {
+            return Ok(());
+        }
+
+        // keep the cursor visible
+        let mut scroll = app.scroll.get();
+        if app.cursor < scroll {
+            scroll = app.cursor;
+        } else if app.cursor >= scroll + height {
+            scroll = app.cursor + 1 - height;
+        }
+        app.scroll.set(scroll);
+
+        for (row, name) in app.matches.iter().skip(scroll).take(height).enumerate() {
+            let mut attr = Attr::default();
+            if scroll + row == app.cursor {
+                attr.effect = Effect::REVERSE;
+            }
+            let group = match name.as_str() {
+                ".." => Some(String::from("../")),
+                key => app
+                    .group_at(key)
+                    .map(|group| format!("{}/ ({})", group.name, group.len())),
+            };
+            if let Some(group) = group {
+                attr.fg = Color::BLUE;
+                attr.effect |= Effect::BOLD;
+                let line: String = group.chars().take(width).collect();
+                canvas.print_with_attr(row, 0, &format!("{:w$}", line, w = width), attr)?;
+                continue;
+            }
+            let value = match (app.store.get(name), app.staging.get(name)) {
+                (Some(current), Some(staged)) => {
+                    attr.fg = Color::YELLOW;
+                    format!("{} → {}", current.value, staged.value)
+                }
+                (Some(current), None) => current.value.to_string(),
+                (None, _) => String::new(),
+            };
+            let line = format!("{:16} {}", name, value);
+            let line: String = line.chars().take(width).collect();
+            canvas.print_with_attr(row, 0, &format!("{:w$}", line, w = width), attr)?;
+        }
+        Ok(())
+    }
+}
+
+impl Draw for Side<'_, '_> {
+    fn draw(&self, canvas: &mut dyn Canvas) -> tuikit::Result<()> {
+        let app = self.0;
+        let (width, height) = canvas.size()?;
+
+        let (lines, focus) = match &app.editor {
+            Some((_, _, editor)) => editor.lines(),
+            None => match app.selected() {
+                Some(param) => (describe(app, &param, width), 0),
+                None => match app
+                    .matches
+                    .get(app.cursor)
+                    .and_then(|key| app.group_at(key))
+                {
+                    Some(group) => {
+                        let description = group.description(width);
+                        let lines = console::strip_ansi_codes(&description)
+                            .lines()
+                            .map(|line| (line.to_string(), Attr::default()))
+                            .collect();
+                        (lines, 0)
+                    }
+                    None => (Vec::new(), 0),
+                },
+            },
+        };
+
+        let skip = (focus + 1).saturating_sub(height);
+        for (row, (line, attr)) in lines.iter().skip(skip).take(height).enumerate() {
+            let line: String = line.chars().take(width).collect();
+            canvas.print_with_attr(row, 0, &line, *attr)?;
+        }
+        Ok(())
+    }
+}
+
+impl Draw for Status<'_, '_> {
+    fn draw(&self, canvas: &mut dyn Canvas) -> tuikit::Result<()> {
+        let app = self.0;
+        let mut col = 0;
+        let mut print = |text: String, attr: Attr| -> tuikit::Result<()> {
+            col += canvas.print_with_attr(0, col, &text, attr)? + 2;
+            Ok(())
+        };
+
+        let (link, color) = link_status(app.link);
+        print(link, color.into())?;
+        print(
+            format!("{}/{} parameters", app.indexed, app.count),
+            Attr::default(),
+        )?;
+        if !app.staging.is_empty() {
+            print(
+                format!("{} pending changes", app.staging.len()),
+                Attr {
+                    fg: Color::YELLOW,
+                    effect: Effect::BOLD,
+                    ..Attr::default()
+                },
+            )?;
+        }
+        if !app.violations.is_empty() {
+            print(
+                format!("{} rule warnings", app.violations.len()),
+                Color::YELLOW.into(),
+            )?;
+        }
+        if let Some((message, color)) = &app.message {
+            print(message.clone(), (*color).into())?;
+        }
+
+        let help = match &app.editor {
+            Some((_, _, editor)) => editor.help(),
+            None if app.grouped => GROUPED_KEYS,
+            None => KEYS,
+        };
+        canvas.print_with_attr(1, 0, help, Effect::DIM.into())?;
+        Ok(())
+    }
+}
+
+impl Draw for Messages<'_, '_> {
+    fn draw(&self, canvas: &mut dyn Canvas) -> tuikit::Result<()> {
+        let console = match self.0.messages {
+            Some(console) => console.lock().unwrap(),
+            None => return Ok(()),
+        };
+        let (width, height) = canvas.size()?;
+        let skip = console.texts.len().saturating_sub(height);
+        for (row, text) in console.texts.iter().skip(skip).enumerate() {
+            let time: chrono::DateTime<chrono::Local> = text.time.into();
+            let line = format!(
+                "{} {:<9} {}",
+                time.format("%H:%M:%S"),
+                statustext::severity_name(text.severity),
+                text.text
+            );
+            let line: String = line.chars().take(width).collect();
+            let look = statustext::look(text.severity);
+            let attr = Attr {
+                fg: match look.colour {
+                    Some(statustext::Colour::Red) => Color::RED,
+                    Some(statustext::Colour::Yellow) => Color::YELLOW,
+                    Some(statustext::Colour::Cyan) => Color::CYAN,
+                    None => Color::default(),
+                },
+                effect: match look.emphasis {
+                    statustext::Emphasis::Bold => Effect::BOLD,
+                    statustext::Emphasis::Normal => Effect::empty(),
+                    statustext::Emphasis::Dim => Effect::DIM,
+                },
+                ..Attr::default()
+            };
+            canvas.print_with_attr(row, 0, &line, attr)?;
+        }
+        Ok(())
+    }
+}
+
+impl Widget for SearchBar<'_, '_> {}
+impl Widget for List<'_, '_> {}
+impl Widget for Side<'_, '_> {}
+impl Widget for Status<'_, '_> {}
+impl Widget for Messages<'_, '_> {}
+
+/// The state of the link as shown in the status bar, given the time since the last HEARTBEAT
+fn link_status(link: Option<Duration>) -> (String, Color) {
+    match link {
+        Some(age) if age < LINK_TIMEOUT => (String::from("● link"), Color::GREEN),
+        Some(age) => (format!("● no HEARTBEAT for {}s", age.as_secs()), Color::RED),
+        None => (String::from("● no HEARTBEAT yet"), Color::RED),
+    }
+}
+
+/// The lines of the side panel describing a parameter
+fn describe(app: &App, param: &Parameter, width: usize) -> Vec<Line> {
+    let def = param.definition();
+    let plain = Attr::default();
+    let bold = Attr::from(Effect::BOLD);
+    let highlight = |color: Color| Attr {
+        fg: color,
+        effect: Effect::BOLD,
+        ..Attr::default()
+    };
+
+    let mut lines = vec![
+        (def.display_name.clone(), highlight(Color::CYAN)),
+        (String::new(), plain),
+    ];
+    let current = app.store.get(&param.name).map_or(param.value, |p| p.value);
+    lines.push((
+        format!(
+            "value:    {}",
+            def.describe_value(current, Some(param.param_type))
+        ),
+        bold,
+    ));
+    if let Some(staged) = app.staging.get(&param.name) {
+        lines.push((
+            format!(
+                "staged:   {}",
+                def.describe_value(staged.value, Some(staged.param_type))
+            ),
+            highlight(Color::YELLOW),
+        ));
+    }
+    if let Some(default) = def.default {
+        lines.push((
+            format!(
+                "default:  {}",
+                def.describe_value(default, Some(param.param_type))
+            ),
+            plain,
+        ));
+    }
+    if let Some(initial) = param.initial.filter(|initial| *initial != current) {
+        lines.push((
+            format!(
+                "was:      {}",
+                def.describe_value(initial, Some(param.param_type))
+            ),
+            Color::YELLOW.into(),
+        ));
+    }
+    if def.read_only {
+        lines.push((String::from("read only"), highlight(Color::RED)));
+    }
+    if def.reboot_required {
+        lines.push((String::from("reboot required"), highlight(Color::YELLOW)));
+    }
+    for violation in &app.violations {
+        if violation.parameters.contains(&param.name) {
+            lines.push((format!("warning: {}", violation), Color::YELLOW.into()));
+        }
+    }
+
+    lines.push((String::new(), plain));
+    for line in textwrap::wrap(&def.description, width.max(1)) {
+        lines.push((line.into_owned(), plain));
+    }
+    lines.push((String::new(), plain));
+
+    match &def.data {
+        Some(DataType::Range { high, low }) => {
+            let mut range = format!("range: [{} - {}]", low, high);
+            if let Some(increment) = def.increment {
+                range += &format!(" in steps of {}", increment);
+            }
+            lines.push((range, plain));
+        }
+        Some(DataType::Values(mapping)) | Some(DataType::Bitmask(mapping)) => {
+            for (k, v) in mapping {
+                lines.push((format!("{:>4} = {}", k, v), plain));
+            }
+        }
+        None => {}
+    }
+    lines
+}
+
+/// Convert the errors of `tuikit`
+pub fn to_io(e: Box<dyn std::error::Error + Send + Sync>) -> io::Error {
+    io::Error::other(e.to_string())
+}
@@ -1,3 +1,4 @@
+use console::style;
 use indicatif::{ProgressBar, ProgressStyle};
 
 pub fn progress_style() -> ProgressStyle {
@@ -34,3 +35,13 @@ where
     progress.finish();
     result
 }
+
+/// Print a warning to stderr
+pub fn warning(msg: &str) {
+    eprintln!("{}: {}", style("warning").yellow().bold(), msg);
+}
+
+/// Print an error to stderr
+pub fn error(msg: &str) {
+    eprintln!("{}: {}", style("error").red().bold(), msg);
+}
@@ -1,3 +1,10 @@
+use std::cmp::Ordering;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use futures::prelude::*;
+
 /// Extract String from mavlink PARAM_VALUE_DATA
 pub fn to_string(input_slice: &[char]) -> String {
     input_slice
@@ -6,9 +13,96 @@ pub fn to_string(input_slice: &[char]) -> String {
         .collect()
 }
 
-/// Extract String from mavlink PARAM_VALUE_DATA
+/// Convert a parameter name to the `param_id` of PARAM_SET and PARAM_REQUEST_READ
+///
+/// MAVLink terminates ids shorter than 16 characters with NUL, padding them with spaces instead
+/// makes the vehicle look for a parameter whose name ends in spaces. Longer names are cut off.
 pub fn to_char_arr(input: &str) -> [char; 16] {
-    let mut result = [' '; 16];
-    input.chars().enumerate().for_each(|(i, e)| result[i] = e);
+    let mut result = [char::from(0); 16];
+    input
+        .chars()
+        .take(16)
+        .enumerate()
+        .for_each(|(i, e)| result[i] = e);
     result
 }
+
+/// Compare names with runs of digits compared by their numeric value
+///
+/// This orders e.g. `SERVO2_FUNCTION` before `SERVO10_FUNCTION`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (x, y) = match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => (x, y),
+        };
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            let (da, db) = (digits(a), digits(b));
+            let (na, nb) = (
+                a[..da].trim_start_matches('0'),
+                b[..db].trim_start_matches('0'),
+            );
+            let ordering = na
+                .len()
+                .cmp(&nb.len())
+                .then_with(|| na.cmp(nb))
+                .then_with(|| da.cmp(&db));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            a = &a[da..];
+            b = &b[db..];
+        } else {
+            if x != y {
+                return x.cmp(&y);
+            }
+            a = &a[x.len_utf8()..];
+            b = &b[y.len_utf8()..];
+        }
+    }
+}
+
+/// The directory of this program below an XDG base directory, e.g. `~/.cache/mavlink-cli`
+///
+/// `variable` names the base directory, e.g. `XDG_CACHE_HOME`, and `default` its location relative
+/// to the home directory if it is not set, e.g. `.cache`.
+pub fn xdg_dir(variable: &str, default: &str) -> PathBuf {
+    let base = std::env::var_os(variable)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(default)
+        });
+    base.join(env!("CARGO_PKG_NAME"))
+}
+
+/// Run the future created by `task` in the background until it completes
+///
+/// Background tasks get a thread of their own rather than being spawned onto smol's executor, as
+/// `MavlinkConnectionHandler::main_loop` blocks the executor's thread while receiving and would
+/// starve them.
+pub fn spawn<F, T>(task: F)
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Future<Output = ()>,
+{
+    std::thread::spawn(move || smol::block_on(task()));
+}
+
+/// Await `future`, failing with a `TimedOut` error after `ttl`
+pub async fn with_timeout<F, T>(ttl: Duration, what: &str, future: F) -> io::Result<T>
+where
+    F: Future<Output = T>,
+{
+    let err = io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("did not receive a {} message in {:?}", what, ttl),
+    );
+    futures::select_biased! {
+        result = future.fuse() => Ok(result),
+        _ = futures::FutureExt::fuse(smol::Timer::after(ttl)) => Err(err),
+    }
+}
@@ -0,0 +1,273 @@
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::time::Duration;
+
+use futures::prelude::*;
+use mavlink::common::*;
+
+use crate::{
+    mavlink_stub::{self, MavlinkConnectionHandler},
+    util::with_timeout,
+};
+
+/// Identifies the firmware running on a vehicle
+///
+/// This is used as key for the on-disk definition cache, as parameters are added, removed and
+/// changed between different releases of an autopilot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Firmware {
+    /// Name of the autopilot, e.g. `ArduPilot`
+    pub autopilot: String,
+    /// Name of the vehicle type, e.g. `ArduCopter`
+    pub vehicle: String,
+    /// Firmware version in the `major.minor.patch` form
+    pub version: String,
+    /// The vehicle this firmware was queried from, if it was
+    pub id: Option<VehicleId>,
+}
+
+/// Tells apart vehicles running the same firmware
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VehicleId {
+    /// The unique id of the flight controller from AUTOPILOT_VERSION
+    Uid(u64),
+    /// The MAVLink system id, for flight controllers without an unique id
+    System(u8),
+}
+
+/// Query the firmware running on the connected vehicle
+///
+/// Waits for the first HEARTBEAT of a vehicle to learn about the autopilot and vehicle type and
+/// then requests AUTOPILOT_VERSION to learn about the firmware version and the unique id of the
+/// flight controller.
+pub async fn firmware(conn: &MavlinkConnectionHandler) -> io::Result<Firmware> {
+    let ttl = Duration::from_secs(3);
+
+    let (system_id, heartbeat) = with_timeout(ttl, "HEARTBEAT", async {
+        let mut frames = conn.tap().await;
+        while let Some(frame) = frames.next().await {
+            match frame.message {
+                MavMessage::HEARTBEAT(data) if is_autopilot(&data) => {
+                    return (frame.header.system_id, data);
+                }
+                _ => {}
+            }
+        }
+        unreachable!("taps never end")
+    })
+    .await?;
+
+    let stream = conn
+        .subscribe(mavlink_stub::message_type(&MavMessage::AUTOPILOT_VERSION(
+            Default::default(),
+        )))
+        .await;
+    let request = MavMessage::COMMAND_LONG(COMMAND_LONG_DATA {
+        command: MavCmd::MAV_CMD_REQUEST_AUTOPILOT_CAPABILITIES,
+        param1: 1.0,
+        ..Default::default()
+    });
+    conn.send_default(&request)?;
+
+    let version = with_timeout(ttl, "AUTOPILOT_VERSION", async {
+        let mut stream = stream;
+        loop {
+            if let Some(MavMessage::AUTOPILOT_VERSION(data)) = stream.next().await {
+                return data;
+            }
+        }
+    })
+    .await?;
+
+    Ok(Firmware {
+        autopilot: autopilot_name(heartbeat.autopilot),
+        vehicle: vehicle_name(heartbeat.mavtype),
+        version: version_string(version.flight_sw_version),
+        id: Some(match version.uid {
+            0 => VehicleId::System(system_id),
+            uid => VehicleId::Uid(uid),
+        }),
+    })
+}
+
+impl Display for VehicleId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VehicleId::Uid(uid) => write!(f, "{:016x}", uid),
+            VehicleId::System(id) => write!(f, "sysid{}", id),
+        }
+    }
+}
+
+impl Display for Firmware {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.autopilot, self.vehicle, self.version)
+    }
+}
+
+/// Whether a HEARTBEAT was sent by an autopilot
+///
+/// Other ground stations may share the link, and gimbals, cameras or companion computers send
+/// HEARTBEATs of their own, which say nothing about the vehicle.
+pub fn is_autopilot(heartbeat: &HEARTBEAT_DATA) -> bool {
+    heartbeat.mavtype != MavType::MAV_TYPE_GCS
+        && heartbeat.autopilot != MavAutopilot::MAV_AUTOPILOT_INVALID
+}
+
+/// The name of the flight mode a vehicle is in, as reported in its HEARTBEAT
+///
+/// ArduPilot always reports its modes as `custom_mode`, which is specific to the vehicle type.
+/// Modes of other autopilots are described by the generic flags of `base_mode`.
+pub fn mode_name(heartbeat: &HEARTBEAT_DATA) -> String {
+    if heartbeat.autopilot == MavAutopilot::MAV_AUTOPILOT_ARDUPILOTMEGA {
+        let modes: &[(u32, &str)] = match vehicle_name(heartbeat.mavtype).as_str() {
+            "ArduCopter" => &COPTER_MODES,
+            "ArduPlane" => &PLANE_MODES,
+            "Rover" => &ROVER_MODES,
+            "ArduSub" => &SUB_MODES,
+            _ => &[],
+        };
+        return match modes.iter().find(|(id, _)| *id == heartbeat.custom_mode) {
+            Some((_, name)) => name.to_string(),
+            None => format!("mode {}", heartbeat.custom_mode),
+        };
+    }
+
+    let flags = [
+        (MavModeFlag::MAV_MODE_FLAG_AUTO_ENABLED, "AUTO"),
+        (MavModeFlag::MAV_MODE_FLAG_GUIDED_ENABLED, "GUIDED"),
+        (MavModeFlag::MAV_MODE_FLAG_STABILIZE_ENABLED, "STABILIZE"),
+        (MavModeFlag::MAV_MODE_FLAG_MANUAL_INPUT_ENABLED, "MANUAL"),
+    ];
+    match flags
+        .iter()
+        .find(|(flag, _)| heartbeat.base_mode.contains(*flag))
+    {
+        Some((_, name)) => name.to_string(),
+        None => format!("custom mode {}", heartbeat.custom_mode),
+    }
+}
+
+// Implementation details
+
+const COPTER_MODES: [(u32, &str); 26] = [
+    (0, "STABILIZE"),
+    (1, "ACRO"),
+    (2, "ALT_HOLD"),
+    (3, "AUTO"),
+    (4, "GUIDED"),
+    (5, "LOITER"),
+    (6, "RTL"),
+    (7, "CIRCLE"),
+    (9, "LAND"),
+    (11, "DRIFT"),
+    (13, "SPORT"),
+    (14, "FLIP"),
+    (15, "AUTOTUNE"),
+    (16, "POSHOLD"),
+    (17, "BRAKE"),
+    (18, "THROW"),
+    (19, "AVOID_ADSB"),
+    (20, "GUIDED_NOGPS"),
+    (21, "SMART_RTL"),
+    (22, "FLOWHOLD"),
+    (23, "FOLLOW"),
+    (24, "ZIGZAG"),
+    (25, "SYSTEMID"),
+    (26, "AUTOROTATE"),
+    (27, "AUTO_RTL"),
+    (28, "TURTLE"),
+];
+
+const PLANE_MODES: [(u32, &str); 25] = [
+    (0, "MANUAL"),
+    (1, "CIRCLE"),
+    (2, "STABILIZE"),
+    (3, "TRAINING"),
+    (4, "ACRO"),
+    (5, "FBWA"),
+    (6, "FBWB"),
+    (7, "CRUISE"),
+    (8, "AUTOTUNE"),
+    (10, "AUTO"),
+    (11, "RTL"),
+    (12, "LOITER"),
+    (13, "TAKEOFF"),
+    (14, "AVOID_ADSB"),
+    (15, "GUIDED"),
+    (17, "QSTABILIZE"),
+    (18, "QHOVER"),
+    (19, "QLOITER"),
+    (20, "QLAND"),
+    (21, "QRTL"),
+    (22, "QAUTOTUNE"),
+    (23, "QACRO"),
+    (24, "THERMAL"),
+    (25, "LOITER_ALT_QLAND"),
+    (26, "AUTOLAND"),
+];
+
+const ROVER_MODES: [(u32, &str); 11] = [
+    (0, "MANUAL"),
+    (1, "ACRO"),
+    (3, "STEERING"),
+    (4, "HOLD"),
+    (5, "LOITER"),
+    (6, "FOLLOW"),
+    (7, "SIMPLE"),
+    (10, "AUTO"),
+    (11, "RTL"),
+    (12, "SMART_RTL"),
+    (15, "GUIDED"),
+];
+
+const SUB_MODES: [(u32, &str); 9] = [
+    (0, "STABILIZE"),
+    (1, "ACRO"),
+    (2, "ALT_HOLD"),
+    (3, "AUTO"),
+    (4, "GUIDED"),
+    (7, "CIRCLE"),
+    (9, "SURFACE"),
+    (16, "POSHOLD"),
+    (19, "MANUAL"),
+];
+
+fn autopilot_name(autopilot: MavAutopilot) -> String {
+    match autopilot {
+        MavAutopilot::MAV_AUTOPILOT_ARDUPILOTMEGA => String::from("ArduPilot"),
+        MavAutopilot::MAV_AUTOPILOT_PX4 => String::from("PX4"),
+        other => format!("{:?}", other)
+            .trim_start_matches("MAV_AUTOPILOT_")
+            .to_string(),
+    }
+}
+
+/// Maps the `MavType` to the naming scheme used by ArduPilot
+fn vehicle_name(mavtype: MavType) -> String {
+    use MavType::*;
+    match mavtype {
+        MAV_TYPE_QUADROTOR | MAV_TYPE_HEXAROTOR | MAV_TYPE_OCTOROTOR | MAV_TYPE_TRICOPTER
+        | MAV_TYPE_COAXIAL | MAV_TYPE_HELICOPTER | MAV_TYPE_DODECAROTOR => {
+            String::from("ArduCopter")
+        }
+        MAV_TYPE_FIXED_WING
+        | MAV_TYPE_VTOL_DUOROTOR
+        | MAV_TYPE_VTOL_QUADROTOR
+        | MAV_TYPE_VTOL_TILTROTOR => String::from("ArduPlane"),
+        MAV_TYPE_GROUND_ROVER | MAV_TYPE_SURFACE_BOAT => String::from("Rover"),
+        MAV_TYPE_SUBMARINE => String::from("ArduSub"),
+        MAV_TYPE_ANTENNA_TRACKER => String::from("AntennaTracker"),
+        other => format!("{:?}", other)
+            .trim_start_matches("MAV_TYPE_")
+            .to_string(),
+    }
+}
+
+/// Decodes the `flight_sw_version` field of AUTOPILOT_VERSION
+///
+/// The version is encoded as `major << 24 | minor << 16 | patch << 8 | release_type`.
+fn version_string(flight_sw_version: u32) -> String {
+    let [major, minor, patch, _release_type] = flight_sw_version.to_be_bytes();
+    format!("{}.{}.{}", major, minor, patch)
+}